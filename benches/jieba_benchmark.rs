@@ -0,0 +1,31 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use jiebars::Jieba;
+
+static SENTENCE: &str =
+    "我是拖拉机学院手扶拖拉机专业的。不用多久，我就会升职加薪，当上CEO，走上人生巅峰。";
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let jieba = Jieba::new().unwrap();
+    //没有标点的长句, 构建 DAG 的开销随句子长度增长
+    let long_sentence =
+        "我是拖拉机学院手扶拖拉机专业的不用多久我就会升职加薪当上总经理走上人生巅峰".repeat(10);
+    c.bench_function("jieba new", |b| b.iter(|| Jieba::new().unwrap()));
+    c.bench_function("cut no hmm", |b| {
+        b.iter(|| jieba.cut(black_box(SENTENCE), false, false))
+    });
+    c.bench_function("cut with hmm", |b| {
+        b.iter(|| jieba.cut(black_box(SENTENCE), false, true))
+    });
+    c.bench_function("cut long sentence", |b| {
+        b.iter(|| jieba.cut(black_box(&long_sentence), false, true))
+    });
+    c.bench_function("cut all", |b| {
+        b.iter(|| jieba.cut(black_box(SENTENCE), true, false))
+    });
+    c.bench_function("cut for search", |b| {
+        b.iter(|| jieba.cut_for_search(black_box(SENTENCE)))
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,97 @@
+#ifndef JIEBARS_H
+#define JIEBARS_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define JIEBA_MODE_PRECISE 0
+
+#define JIEBA_MODE_FULL 1
+
+#define JIEBA_MODE_SEARCH 2
+
+#define JIEBA_MODE_SEARCH_FINE 3
+
+#define JIEBA_TOKENIZE_DEFAULT 0
+
+#define JIEBA_TOKENIZE_SEARCH 1
+
+typedef enum JiebaStatus {
+  JIEBA_STATUS_OK = 0,
+  JIEBA_STATUS_INVALID_ARGUMENT = 1,
+  JIEBA_STATUS_INVALID_UTF8 = 2,
+  JIEBA_STATUS_IO = 3,
+  JIEBA_STATUS_INVALID_DICT = 4,
+  JIEBA_STATUS_INVALID_MODEL = 5,
+  JIEBA_STATUS_UNEXPECTED = 6,
+  JIEBA_STATUS_PANIC = 7,
+} JiebaStatus;
+
+typedef struct Jieba Jieba;
+
+typedef struct JiebaWords {
+  char **words;
+  size_t len;
+} JiebaWords;
+
+typedef struct JiebaToken {
+  char *word;
+  size_t start;
+  size_t end;
+  size_t char_start;
+  size_t char_end;
+} JiebaToken;
+
+typedef struct JiebaTokens {
+  struct JiebaToken *tokens;
+  size_t len;
+} JiebaTokens;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+enum JiebaStatus jieba_new(struct Jieba **out);
+
+enum JiebaStatus jieba_new_with_dict(const char *dict_path, struct Jieba **out);
+
+void jieba_free(struct Jieba *jieba);
+
+enum JiebaStatus jieba_load_user_dict(struct Jieba *jieba, const char *path);
+
+enum JiebaStatus jieba_add_word(struct Jieba *jieba,
+                                const char *word,
+                                double freq,
+                                const char *tag,
+                                double *out_freq);
+
+enum JiebaStatus jieba_del_word(struct Jieba *jieba, const char *word);
+
+enum JiebaStatus jieba_cut(const struct Jieba *jieba,
+                           const char *text,
+                           size_t len,
+                           int mode,
+                           bool hmm,
+                           struct JiebaWords **out);
+
+void jieba_words_free(struct JiebaWords *words);
+
+enum JiebaStatus jieba_tokenize(const struct Jieba *jieba,
+                                const char *text,
+                                size_t len,
+                                int mode,
+                                bool hmm,
+                                struct JiebaTokens **out);
+
+void jieba_tokens_free(struct JiebaTokens *tokens);
+
+const char *jieba_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* JIEBARS_H */
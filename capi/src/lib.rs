@@ -0,0 +1,491 @@
+// jiebars 的 C 接口, 头文件为 include/jiebars.h
+//
+// 返回 JiebaStatus 的函数失败时可以用 jieba_last_error 获取错误信息,
+// 输出参数只在返回 JIEBA_STATUS_OK 时有效. 所有字符串都是 UTF-8 编码
+// 同一个 Jieba 可以在多个线程中同时分词, 但修改词典的函数 (jieba_load_user_dict、
+// jieba_add_word、jieba_del_word) 不能与同一个 Jieba 上的其他调用同时进行, 需要调用方加锁
+#![allow(clippy::missing_safety_doc)]
+
+use jiebars::{CutMode, CutOptions, Error, Jieba, JiebaBuilder, TokenizeMode};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+pub const JIEBA_MODE_PRECISE: c_int = 0;
+pub const JIEBA_MODE_FULL: c_int = 1;
+pub const JIEBA_MODE_SEARCH: c_int = 2;
+pub const JIEBA_MODE_SEARCH_FINE: c_int = 3;
+
+pub const JIEBA_TOKENIZE_DEFAULT: c_int = 0;
+pub const JIEBA_TOKENIZE_SEARCH: c_int = 1;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JiebaStatus {
+    Ok = 0,
+    //参数为空指针或取值无效
+    InvalidArgument = 1,
+    //文本不是 UTF-8 编码
+    InvalidUtf8 = 2,
+    //读取文件失败
+    Io = 3,
+    //词典格式错误
+    InvalidDict = 4,
+    //模型格式错误
+    InvalidModel = 5,
+    Unexpected = 6,
+    //jiebars 内部 panic
+    Panic = 7,
+}
+
+pub struct JiebaHandle {
+    inner: Jieba,
+}
+
+#[repr(C)]
+pub struct JiebaWords {
+    pub words: *mut *mut c_char,
+    pub len: usize,
+}
+
+//start/end 为字节位置, char_start/char_end 为字符位置, 都是左闭右开
+#[repr(C)]
+pub struct JiebaToken {
+    pub word: *mut c_char,
+    pub start: usize,
+    pub end: usize,
+    pub char_start: usize,
+    pub char_end: usize,
+}
+
+#[repr(C)]
+pub struct JiebaTokens {
+    pub tokens: *mut JiebaToken,
+    pub len: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+struct Failure(JiebaStatus, String);
+
+impl From<Error> for Failure {
+    fn from(e: Error) -> Self {
+        let status = match e {
+            Error::UnexpectIO(..) => JiebaStatus::Io,
+            Error::Unexpected(_) => JiebaStatus::Unexpected,
+            Error::InvalidDictEntry(..) | Error::InvalidCompiledDict(_) => JiebaStatus::InvalidDict,
+            Error::InvalidModelEntry(..) => JiebaStatus::InvalidModel,
+            Error::InvalidOption(_) | Error::InvalidFrequency(..) => JiebaStatus::InvalidArgument,
+        };
+        Failure(status, e.to_string())
+    }
+}
+
+fn invalid(msg: &str) -> Failure {
+    Failure(JiebaStatus::InvalidArgument, msg.to_string())
+}
+
+fn set_last_error(msg: String) {
+    let msg = CString::new(msg.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = msg);
+}
+
+//执行 f 并把错误和 panic 转为返回码
+fn guard<F: FnOnce() -> Result<(), Failure>>(f: F) -> JiebaStatus {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => {
+            set_last_error(String::new());
+            JiebaStatus::Ok
+        }
+        Ok(Err(Failure(status, msg))) => {
+            set_last_error(msg);
+            status
+        }
+        Err(_) => {
+            set_last_error("panic in jiebars".to_string());
+            JiebaStatus::Panic
+        }
+    }
+}
+
+unsafe fn c_str<'a>(s: *const c_char, name: &str) -> Result<&'a str, Failure> {
+    if s.is_null() {
+        return Err(invalid(&format!("{} is null", name)));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|e| Failure(JiebaStatus::InvalidUtf8, format!("{}: {}", name, e)))
+}
+
+unsafe fn utf8_text<'a>(text: *const c_char, len: usize) -> Result<&'a str, Failure> {
+    if text.is_null() {
+        return Err(invalid("text is null"));
+    }
+    let bytes = std::slice::from_raw_parts(text as *const u8, len);
+    if bytes.contains(&0) {
+        return Err(invalid("text contains NUL"));
+    }
+    std::str::from_utf8(bytes).map_err(|e| Failure(JiebaStatus::InvalidUtf8, e.to_string()))
+}
+
+unsafe fn handle<'a>(jieba: *const JiebaHandle) -> Result<&'a JiebaHandle, Failure> {
+    jieba.as_ref().ok_or_else(|| invalid("jieba is null"))
+}
+
+fn to_c_string(s: &str) -> *mut c_char {
+    //输入的文本中没有 \0, 词中也不会有
+    CString::new(s).unwrap_or_default().into_raw()
+}
+
+fn new_handle(builder: JiebaBuilder, out: *mut *mut JiebaHandle) -> Result<(), Failure> {
+    if out.is_null() {
+        return Err(invalid("out is null"));
+    }
+    let inner = builder.build()?;
+    unsafe { *out = Box::into_raw(Box::new(JiebaHandle { inner })) };
+    Ok(())
+}
+
+//使用内置词典创建, 用完后调用 jieba_free 释放
+#[no_mangle]
+pub unsafe extern "C" fn jieba_new(out: *mut *mut JiebaHandle) -> JiebaStatus {
+    guard(|| new_handle(JiebaBuilder::new(), out))
+}
+
+//使用文本词典 dict_path 代替内置词典
+#[no_mangle]
+pub unsafe extern "C" fn jieba_new_with_dict(
+    dict_path: *const c_char,
+    out: *mut *mut JiebaHandle,
+) -> JiebaStatus {
+    guard(|| {
+        let path = c_str(dict_path, "dict_path")?;
+        new_handle(JiebaBuilder::new().dict_path(path), out)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jieba_free(jieba: *mut JiebaHandle) {
+    if !jieba.is_null() {
+        drop(Box::from_raw(jieba));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jieba_load_user_dict(
+    jieba: *mut JiebaHandle,
+    path: *const c_char,
+) -> JiebaStatus {
+    guard(|| {
+        let jieba = jieba.as_mut().ok_or_else(|| invalid("jieba is null"))?;
+        let path = c_str(path, "path")?;
+        jieba.inner.load_user_dict(path)?;
+        Ok(())
+    })
+}
+
+// 加入一个词, freq 小于 0 时自动计算能让该词被切分出来的词频, 为 NaN 或无穷大时返回
+// JIEBA_STATUS_INVALID_ARGUMENT. freq 为 0 时与 jieba_del_word 相同, 该词不会再被切分出来
+// tag 可以为 NULL, out_freq 不为 NULL 时写入实际使用的词频
+// 不能与同一个 Jieba 上的 jieba_cut 等调用同时进行
+#[no_mangle]
+pub unsafe extern "C" fn jieba_add_word(
+    jieba: *mut JiebaHandle,
+    word: *const c_char,
+    freq: f64,
+    tag: *const c_char,
+    out_freq: *mut f64,
+) -> JiebaStatus {
+    guard(|| {
+        let jieba = jieba.as_mut().ok_or_else(|| invalid("jieba is null"))?;
+        let word = c_str(word, "word")?;
+        let tag = if tag.is_null() {
+            None
+        } else {
+            Some(c_str(tag, "tag")?)
+        };
+        let freq = if freq.is_finite() && freq < 0f64 {
+            None
+        } else {
+            Some(freq)
+        };
+        let freq = jieba.inner.add_word(word, freq, tag)?;
+        if !out_freq.is_null() {
+            *out_freq = freq;
+        }
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jieba_del_word(
+    jieba: *mut JiebaHandle,
+    word: *const c_char,
+) -> JiebaStatus {
+    guard(|| {
+        let jieba = jieba.as_mut().ok_or_else(|| invalid("jieba is null"))?;
+        jieba.inner.del_word(c_str(word, "word")?);
+        Ok(())
+    })
+}
+
+// 切分 text 的前 len 个字节, mode 为 JIEBA_MODE_*, 全模式下 hmm 无效
+// 结果用 jieba_words_free 释放
+#[no_mangle]
+pub unsafe extern "C" fn jieba_cut(
+    jieba: *const JiebaHandle,
+    text: *const c_char,
+    len: usize,
+    mode: c_int,
+    hmm: bool,
+    out: *mut *mut JiebaWords,
+) -> JiebaStatus {
+    guard(|| {
+        let jieba = handle(jieba)?;
+        let text = utf8_text(text, len)?;
+        if out.is_null() {
+            return Err(invalid("out is null"));
+        }
+        let mode = match mode {
+            JIEBA_MODE_PRECISE => CutMode::Precise,
+            JIEBA_MODE_FULL => CutMode::Full,
+            JIEBA_MODE_SEARCH => CutMode::Search,
+            JIEBA_MODE_SEARCH_FINE => CutMode::SearchFine,
+            _ => return Err(invalid(&format!("unknown mode {}", mode))),
+        };
+        let opts = CutOptions {
+            hmm,
+            ..Default::default()
+        };
+        let words: Vec<*mut c_char> = jieba
+            .inner
+            .cut_with(text, mode, opts)
+            .iter()
+            .map(|w| to_c_string(w))
+            .collect();
+        let mut words = words.into_boxed_slice();
+        let result = JiebaWords {
+            words: words.as_mut_ptr(),
+            len: words.len(),
+        };
+        std::mem::forget(words);
+        *out = Box::into_raw(Box::new(result));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jieba_words_free(words: *mut JiebaWords) {
+    if words.is_null() {
+        return;
+    }
+    let words = Box::from_raw(words);
+    let slice = Box::from_raw(ptr::slice_from_raw_parts_mut(words.words, words.len));
+    for word in slice.iter() {
+        drop(CString::from_raw(*word));
+    }
+}
+
+// 切分并给出每个词的位置, mode 为 JIEBA_TOKENIZE_*
+// 结果用 jieba_tokens_free 释放
+#[no_mangle]
+pub unsafe extern "C" fn jieba_tokenize(
+    jieba: *const JiebaHandle,
+    text: *const c_char,
+    len: usize,
+    mode: c_int,
+    hmm: bool,
+    out: *mut *mut JiebaTokens,
+) -> JiebaStatus {
+    guard(|| {
+        let jieba = handle(jieba)?;
+        let text = utf8_text(text, len)?;
+        if out.is_null() {
+            return Err(invalid("out is null"));
+        }
+        let mode = match mode {
+            JIEBA_TOKENIZE_DEFAULT => TokenizeMode::Default,
+            JIEBA_TOKENIZE_SEARCH => TokenizeMode::Search,
+            _ => return Err(invalid(&format!("unknown mode {}", mode))),
+        };
+        let tokens: Vec<JiebaToken> = jieba
+            .inner
+            .tokenize(text, mode, hmm)
+            .iter()
+            .map(|t| JiebaToken {
+                word: to_c_string(t.word),
+                start: t.start,
+                end: t.end,
+                char_start: t.char_start,
+                char_end: t.char_end,
+            })
+            .collect();
+        let mut tokens = tokens.into_boxed_slice();
+        let result = JiebaTokens {
+            tokens: tokens.as_mut_ptr(),
+            len: tokens.len(),
+        };
+        std::mem::forget(tokens);
+        *out = Box::into_raw(Box::new(result));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn jieba_tokens_free(tokens: *mut JiebaTokens) {
+    if tokens.is_null() {
+        return;
+    }
+    let tokens = Box::from_raw(tokens);
+    let slice = Box::from_raw(ptr::slice_from_raw_parts_mut(tokens.tokens, tokens.len));
+    for token in slice.iter() {
+        drop(CString::from_raw(token.word));
+    }
+}
+
+// 当前线程最近一次调用的错误信息, 调用成功时为空字符串
+// 返回的指针在当前线程下一次调用 jieba_* 之前有效, 不需要释放
+#[no_mangle]
+pub extern "C" fn jieba_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn words(words: *const JiebaWords) -> Vec<String> {
+        let words = &*words;
+        std::slice::from_raw_parts(words.words, words.len)
+            .iter()
+            .map(|w| CStr::from_ptr(*w).to_str().unwrap().to_string())
+            .collect()
+    }
+
+    unsafe fn last_error() -> String {
+        CStr::from_ptr(jieba_last_error())
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_capi() {
+        unsafe {
+            let mut jieba = ptr::null_mut();
+            assert_eq!(jieba_new(&mut jieba), JiebaStatus::Ok);
+
+            let text = "他来到了网易杭研大厦";
+            let mut out = ptr::null_mut();
+            let status = jieba_cut(
+                jieba,
+                text.as_ptr() as *const c_char,
+                text.len(),
+                JIEBA_MODE_PRECISE,
+                false,
+                &mut out,
+            );
+            assert_eq!(status, JiebaStatus::Ok);
+            assert_eq!(
+                words(out),
+                vec!["他", "来到", "了", "网易", "杭", "研", "大厦"]
+            );
+            jieba_words_free(out);
+
+            let mut freq = 0f64;
+            let status = jieba_add_word(jieba, c"杭研".as_ptr(), -1f64, c"nt".as_ptr(), &mut freq);
+            assert_eq!(status, JiebaStatus::Ok);
+            assert!(freq > 0f64);
+
+            let mut out = ptr::null_mut();
+            let status = jieba_tokenize(
+                jieba,
+                text.as_ptr() as *const c_char,
+                text.len(),
+                JIEBA_TOKENIZE_DEFAULT,
+                false,
+                &mut out,
+            );
+            assert_eq!(status, JiebaStatus::Ok);
+            let tokens = std::slice::from_raw_parts((*out).tokens, (*out).len);
+            assert_eq!(CStr::from_ptr(tokens[4].word).to_str().unwrap(), "杭研");
+            assert_eq!((tokens[4].start, tokens[4].end), (18, 24));
+            assert_eq!((tokens[4].char_start, tokens[4].char_end), (6, 8));
+            jieba_tokens_free(out);
+
+            jieba_free(jieba);
+        }
+    }
+
+    #[test]
+    fn test_capi_errors() {
+        unsafe {
+            let mut jieba = ptr::null_mut();
+            let status = jieba_new_with_dict(c"no/such/dict.txt".as_ptr(), &mut jieba);
+            assert_eq!(status, JiebaStatus::Io);
+            assert!(last_error().contains("no/such/dict.txt"));
+            assert!(jieba.is_null());
+
+            assert_eq!(jieba_new(ptr::null_mut()), JiebaStatus::InvalidArgument);
+            assert_eq!(jieba_new(&mut jieba), JiebaStatus::Ok);
+            assert_eq!(last_error(), "");
+
+            let bad = [0xffu8, 0xfe];
+            let mut out = ptr::null_mut();
+            let status = jieba_cut(
+                jieba,
+                bad.as_ptr() as *const c_char,
+                bad.len(),
+                JIEBA_MODE_PRECISE,
+                true,
+                &mut out,
+            );
+            assert_eq!(status, JiebaStatus::InvalidUtf8);
+            let status = jieba_cut(jieba, c"x".as_ptr(), 2, JIEBA_MODE_PRECISE, true, &mut out);
+            assert_eq!(status, JiebaStatus::InvalidArgument);
+            let status = jieba_cut(jieba, c"x".as_ptr(), 1, 42, true, &mut out);
+            assert_eq!(status, JiebaStatus::InvalidArgument);
+            assert!(last_error().contains("42"));
+            let status = jieba_load_user_dict(jieba, ptr::null());
+            assert_eq!(status, JiebaStatus::InvalidArgument);
+            let word = c"杭研".as_ptr();
+            for freq in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+                let status = jieba_add_word(jieba, word, freq, ptr::null(), ptr::null_mut());
+                assert_eq!(status, JiebaStatus::InvalidArgument);
+            }
+            jieba_free(jieba);
+        }
+    }
+
+    #[test]
+    fn test_header() {
+        //头文件需要声明所有导出的函数
+        let header = include_str!("../include/jiebars.h");
+        let source = include_str!("lib.rs");
+        let mut count = 0;
+        for line in source.lines() {
+            if let Some(rest) = line.strip_prefix("pub unsafe extern \"C\" fn ") {
+                let name = rest.split('(').next().unwrap();
+                assert!(
+                    header.contains(&format!(" {}(", name)),
+                    "{} not in header",
+                    name
+                );
+                count += 1;
+            } else if let Some(rest) = line.strip_prefix("pub extern \"C\" fn ") {
+                let name = rest.split('(').next().unwrap();
+                assert!(
+                    header.contains(&format!("{}(", name)),
+                    "{} not in header",
+                    name
+                );
+                count += 1;
+            }
+        }
+        assert_eq!(count, 11);
+    }
+}
@@ -0,0 +1,49 @@
+mod textrank;
+mod tfidf;
+
+use crate::Jieba;
+use std::collections::HashSet;
+
+pub use self::textrank::{TextRank, DEFAULT_ALLOW_POS};
+pub use self::tfidf::TfIdf;
+
+//默认停用词, 与 jieba.analyse 相同
+pub(crate) static STOP_WORDS: [&str; 31] = [
+    "the", "of", "is", "and", "to", "in", "that", "we", "for", "an", "are", "by", "be", "as", "on",
+    "with", "can", "if", "from", "which", "you", "it", "this", "then", "at", "have", "all", "not",
+    "one", "has", "or",
+];
+
+pub(crate) fn default_stop_words() -> HashSet<String> {
+    STOP_WORDS.iter().map(|w| w.to_string()).collect()
+}
+
+//关键词及其权重
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyword {
+    pub keyword: String,
+    pub weight: f64,
+}
+
+pub trait KeywordExtract {
+    //提取权重最高的 top_k 个关键词, allow_pos 不为空时只保留这些词性的词
+    fn extract_tags(
+        &self,
+        jieba: &Jieba,
+        text: &str,
+        top_k: usize,
+        allow_pos: &[&str],
+    ) -> Vec<Keyword>;
+}
+
+//按权重从高到低排序, 权重相同时按词排序保证结果稳定
+pub(crate) fn sort_keywords(mut keywords: Vec<Keyword>, top_k: usize) -> Vec<Keyword> {
+    keywords.sort_by(|a, b| {
+        b.weight
+            .partial_cmp(&a.weight)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.keyword.cmp(&b.keyword))
+    });
+    keywords.truncate(top_k);
+    keywords
+}
@@ -0,0 +1,156 @@
+use super::{default_stop_words, sort_keywords, Keyword, KeywordExtract};
+use crate::Jieba;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+//jieba.analyse.textrank 默认只保留的词性
+pub static DEFAULT_ALLOW_POS: [&str; 4] = ["ns", "n", "vn", "v"];
+
+// 基于 TextRank 的关键词提取, 与 jieba.analyse.textrank 相同
+// 在固定大小的窗口内共现的词之间连一条边, 边的权重为共现次数, 然后在无向带权图上迭代计算 PageRank
+pub struct TextRank {
+    window: usize,
+    iterations: usize,
+    damping: f64,
+    stop_words: HashSet<String>,
+}
+
+impl Default for TextRank {
+    fn default() -> Self {
+        TextRank::new()
+    }
+}
+
+impl TextRank {
+    pub fn new() -> TextRank {
+        TextRank {
+            window: 5,
+            iterations: 10,
+            damping: 0.85,
+            stop_words: default_stop_words(),
+        }
+    }
+
+    //共现窗口的大小, 默认为 5
+    pub fn set_window(&mut self, window: usize) {
+        self.window = window.max(2);
+    }
+
+    //PageRank 迭代次数, 默认为 10
+    pub fn set_iterations(&mut self, iterations: usize) {
+        self.iterations = iterations;
+    }
+
+    //阻尼系数, 默认为 0.85
+    pub fn set_damping(&mut self, damping: f64) {
+        self.damping = damping;
+    }
+
+    pub fn add_stop_word(&mut self, word: &str) {
+        self.stop_words.insert(word.to_string());
+    }
+
+    pub fn remove_stop_word(&mut self, word: &str) {
+        self.stop_words.remove(word);
+    }
+
+    fn is_stop_word(&self, word: &str) -> bool {
+        word.trim().chars().count() < 2 || self.stop_words.contains(&word.to_lowercase())
+    }
+
+    fn rank<'a>(&self, graph: &BTreeMap<&'a str, Vec<(&'a str, f64)>>) -> HashMap<&'a str, f64> {
+        let wsdef = 1f64 / (graph.len().max(1) as f64);
+        let mut ws: HashMap<&str, f64> = graph.keys().map(|n| (*n, wsdef)).collect();
+        let out_sum: HashMap<&str, f64> = graph
+            .iter()
+            .map(|(n, out)| (*n, out.iter().map(|e| e.1).sum()))
+            .collect();
+        for _ in 0..self.iterations {
+            for (n, edges) in graph.iter() {
+                let s: f64 = edges.iter().map(|(m, w)| w / out_sum[m] * ws[m]).sum();
+                ws.insert(n, (1f64 - self.damping) + self.damping * s);
+            }
+        }
+        //与 jieba 相同的归一化方式
+        let min_rank = ws.values().cloned().fold(f64::MAX, f64::min);
+        let max_rank = ws.values().cloned().fold(f64::MIN_POSITIVE, f64::max);
+        for w in ws.values_mut() {
+            *w = (*w - min_rank / 10f64) / (max_rank - min_rank / 10f64);
+        }
+        ws
+    }
+}
+
+impl KeywordExtract for TextRank {
+    fn extract_tags(
+        &self,
+        jieba: &Jieba,
+        text: &str,
+        top_k: usize,
+        allow_pos: &[&str],
+    ) -> Vec<Keyword> {
+        let tags = jieba.posseg(text, true);
+        let filter = |i: usize| {
+            (allow_pos.is_empty() || allow_pos.contains(&tags[i].tag))
+                && !self.is_stop_word(tags[i].word)
+        };
+        let mut cm: HashMap<(&str, &str), f64> = HashMap::new();
+        for i in 0..tags.len() {
+            if !filter(i) {
+                continue;
+            }
+            for j in i + 1..(i + self.window).min(tags.len()) {
+                if filter(j) {
+                    *cm.entry((tags[i].word, tags[j].word)).or_insert(0f64) += 1f64;
+                }
+            }
+        }
+        let mut graph: BTreeMap<&str, Vec<(&str, f64)>> = BTreeMap::new();
+        for ((a, b), w) in cm {
+            graph.entry(a).or_default().push((b, w));
+            graph.entry(b).or_default().push((a, w));
+        }
+        let keywords = self
+            .rank(&graph)
+            .into_iter()
+            .map(|(word, weight)| Keyword {
+                keyword: word.to_string(),
+                weight,
+            })
+            .collect();
+        sort_keywords(keywords, top_k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_textrank() {
+        let jieba = Jieba::new().unwrap();
+        let textrank = TextRank::new();
+        let text = "此外，公司拟对全资子公司吉林欧亚置业有限公司增资4.3亿元，增资后，吉林欧亚置业注册资本由7000万元增加到5亿元。吉林欧亚置业主要经营范围为房地产开发及百货零售等业务。目前在建吉林欧亚城市商业综合体项目。2013年，实现营业收入0万元，实现净利润-139.13万元。";
+        let keywords = textrank.extract_tags(&jieba, text, 6, &DEFAULT_ALLOW_POS);
+        assert_eq!(keywords.len(), 6);
+        assert_eq!(keywords[0].keyword, "吉林");
+        assert_eq!(keywords[0].weight, 1f64);
+        assert!(keywords.windows(2).all(|w| w[0].weight >= w[1].weight));
+        for k in keywords {
+            assert!(DEFAULT_ALLOW_POS.contains(&jieba.tag(&k.keyword).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_textrank_window() {
+        let jieba = Jieba::new().unwrap();
+        let mut textrank = TextRank::new();
+        textrank.set_window(2);
+        textrank.set_iterations(20);
+        let keywords = textrank.extract_tags(&jieba, "", 10, &[]);
+        assert!(keywords.is_empty());
+        let keywords = textrank.extract_tags(&jieba, "北京大学 清华大学", 10, &[]);
+        assert!(keywords.is_empty());
+        let keywords = textrank.extract_tags(&jieba, "北京大学清华大学", 10, &[]);
+        assert_eq!(keywords.len(), 2);
+    }
+}
@@ -0,0 +1,165 @@
+use super::{default_stop_words, sort_keywords, Keyword, KeywordExtract};
+use crate::error::{Error, JResult};
+use crate::Jieba;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+static DEFAULT_IDF: &str = include_str!("../data/idf.txt");
+
+// 基于 TF-IDF 的关键词提取, 与 jieba.analyse.extract_tags 相同
+// 词的权重为 词频 / 总词数 * IDF, 不在 IDF 表中的词使用 IDF 的中位数
+pub struct TfIdf {
+    idf: HashMap<String, f64>,
+    median_idf: f64,
+    stop_words: HashSet<String>,
+}
+
+impl TfIdf {
+    pub fn new() -> JResult<TfIdf> {
+        let mut tfidf = TfIdf {
+            idf: HashMap::new(),
+            median_idf: 0f64,
+            stop_words: default_stop_words(),
+        };
+        tfidf.load_idf_from_reader(DEFAULT_IDF.as_bytes())?;
+        Ok(tfidf)
+    }
+
+    //使用自定义的 IDF 表替换默认的 IDF 表, 每行格式为 `word idf`
+    pub fn load_idf<P: AsRef<Path>>(&mut self, path: P) -> JResult<()> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|e| Error::UnexpectIO(format!("open {}", path.display()), e))?;
+        self.load_idf_from_reader(BufReader::new(file))
+    }
+
+    pub fn load_idf_from_reader<R: BufRead>(&mut self, reader: R) -> JResult<()> {
+        let mut idf: HashMap<String, f64> = HashMap::new();
+        for (i, res_line) in reader.lines().enumerate() {
+            let line = res_line?;
+            let elem = line.split_whitespace().collect::<Vec<&str>>();
+            if elem.is_empty() {
+                continue;
+            }
+            let freq = match elem[..] {
+                [_, f] => f.parse::<f64>().ok(),
+                _ => None,
+            };
+            match freq {
+                Some(f) => idf.insert(elem[0].to_string(), f),
+                None => return Err(Error::InvalidDictEntry(i + 1, line.to_string())),
+            };
+        }
+        if idf.is_empty() {
+            return Err(Error::Unexpected("empty idf table".to_string()));
+        }
+        let mut values: Vec<f64> = idf.values().cloned().collect();
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        self.median_idf = values[values.len() / 2];
+        self.idf = idf;
+        Ok(())
+    }
+
+    //停用词文件每行一个词, 加入到已有的停用词中
+    pub fn load_stop_words<P: AsRef<Path>>(&mut self, path: P) -> JResult<()> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|e| Error::UnexpectIO(format!("open {}", path.display()), e))?;
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let word = line.trim();
+            if !word.is_empty() {
+                self.stop_words.insert(word.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_stop_word(&mut self, word: &str) {
+        self.stop_words.insert(word.to_string());
+    }
+
+    pub fn remove_stop_word(&mut self, word: &str) {
+        self.stop_words.remove(word);
+    }
+
+    fn is_stop_word(&self, word: &str) -> bool {
+        word.trim().chars().count() < 2 || self.stop_words.contains(&word.to_lowercase())
+    }
+}
+
+impl KeywordExtract for TfIdf {
+    fn extract_tags(
+        &self,
+        jieba: &Jieba,
+        text: &str,
+        top_k: usize,
+        allow_pos: &[&str],
+    ) -> Vec<Keyword> {
+        let mut freq: HashMap<&str, f64> = HashMap::new();
+        if allow_pos.is_empty() {
+            for word in jieba.cut(text, false, true) {
+                if !self.is_stop_word(word) {
+                    *freq.entry(word).or_insert(0f64) += 1f64;
+                }
+            }
+        } else {
+            for tag in jieba.posseg(text, true) {
+                if allow_pos.contains(&tag.tag) && !self.is_stop_word(tag.word) {
+                    *freq.entry(tag.word).or_insert(0f64) += 1f64;
+                }
+            }
+        }
+        let total: f64 = freq.values().sum();
+        let keywords = freq
+            .into_iter()
+            .map(|(word, f)| Keyword {
+                keyword: word.to_string(),
+                weight: f * self.idf.get(word).cloned().unwrap_or(self.median_idf) / total,
+            })
+            .collect();
+        sort_keywords(keywords, top_k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_tags() {
+        let jieba = Jieba::new().unwrap();
+        let tfidf = TfIdf::new().unwrap();
+        let text = "此外，公司拟对全资子公司吉林欧亚置业有限公司增资4.3亿元，增资后，吉林欧亚置业注册资本由7000万元增加到5亿元。吉林欧亚置业主要经营范围为房地产开发及百货零售等业务。";
+        let keywords = tfidf.extract_tags(&jieba, text, 5, &[]);
+        assert_eq!(keywords.len(), 5);
+        assert_eq!(keywords[0].keyword, "欧亚");
+        assert!(keywords.windows(2).all(|w| w[0].weight >= w[1].weight));
+
+        let keywords = tfidf.extract_tags(&jieba, text, 20, &["ns", "n"]);
+        for k in keywords {
+            assert!(["ns", "n"].contains(&jieba.tag(&k.keyword).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_custom_idf() {
+        let jieba = Jieba::new().unwrap();
+        let mut tfidf = TfIdf::new().unwrap();
+        tfidf
+            .load_idf_from_reader("北京 1.0\n天安门 10.0\n".as_bytes())
+            .unwrap();
+        tfidf.add_stop_word("天安门");
+        let keywords = tfidf.extract_tags(&jieba, "我爱北京天安门", 10, &[]);
+        assert_eq!(
+            keywords,
+            vec![Keyword {
+                keyword: "北京".to_string(),
+                weight: 1.0,
+            }]
+        );
+        assert!(tfidf.load_idf_from_reader("北京\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,455 @@
+use jiebars::analyse::{Keyword, KeywordExtract, TextRank, TfIdf};
+use jiebars::{CutMode, CutOptions, Jieba, JiebaBuilder, TokenizeMode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::Read;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::process;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use tiny_http::{Header, Method, Response, Server};
+
+const USAGE: &str = "usage: jiebars-server [options]
+
+options:
+  -l, --listen ADDR       监听地址, 默认为 127.0.0.1:8000
+  -t, --threads N         处理请求的线程数, 默认为 4
+  -D, --dict FILE         使用 FILE 代替内置词典
+  -u, --user-dict FILE    加载用户词典, 可以指定多次
+  -b, --max-body BYTES    请求体的最大字节数, 默认为 1048576
+  -h, --help              显示帮助
+
+endpoints (POST, JSON):
+  /cut             {\"text\", \"mode\": \"precise|full|search|search_fine\", \"hmm\"}
+  /cut_for_search  {\"text\", \"hmm\"}
+  /tokenize        {\"text\", \"mode\": \"default|search\", \"hmm\"}
+  /extract_tags    {\"text\", \"top_k\", \"allow_pos\", \"method\": \"tfidf|textrank\"}
+  /add_word        {\"word\", \"freq\", \"tag\"}";
+
+//请求体默认最多 1 MiB
+const DEFAULT_MAX_BODY: u64 = 1 << 20;
+
+//所有线程共享的状态, add_word 需要写锁
+struct State {
+    jieba: RwLock<Jieba>,
+    tfidf: TfIdf,
+    textrank: TextRank,
+    max_body: u64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_top_k() -> usize {
+    20
+}
+
+#[derive(Deserialize)]
+struct CutRequest {
+    text: String,
+    #[serde(default)]
+    mode: Option<String>,
+    #[serde(default = "default_true")]
+    hmm: bool,
+}
+
+#[derive(Deserialize)]
+struct ExtractRequest {
+    text: String,
+    #[serde(default = "default_top_k")]
+    top_k: usize,
+    #[serde(default)]
+    allow_pos: Vec<String>,
+    #[serde(default)]
+    method: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AddWordRequest {
+    word: String,
+    #[serde(default)]
+    freq: Option<f64>,
+    #[serde(default)]
+    tag: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TokenResponse<'a> {
+    word: &'a str,
+    start: usize,
+    end: usize,
+    char_start: usize,
+    char_end: usize,
+}
+
+fn parse<'a, T: Deserialize<'a>>(body: &'a str) -> Result<T, (u16, String)> {
+    serde_json::from_str(body).map_err(|e| (400, format!("invalid request: {}", e)))
+}
+
+fn cut_mode(mode: Option<&str>) -> Result<CutMode, (u16, String)> {
+    match mode.unwrap_or("precise") {
+        "precise" => Ok(CutMode::Precise),
+        "full" => Ok(CutMode::Full),
+        "search" => Ok(CutMode::Search),
+        "search_fine" => Ok(CutMode::SearchFine),
+        m => Err((400, format!("unknown mode {:?}", m))),
+    }
+}
+
+fn keywords_json(keywords: Vec<Keyword>) -> Value {
+    let keywords: Vec<Value> = keywords
+        .into_iter()
+        .map(|k| json!({"keyword": k.keyword, "weight": k.weight}))
+        .collect();
+    json!({ "keywords": keywords })
+}
+
+fn poisoned<T>(_: T) -> (u16, String) {
+    (500, "lock poisoned".to_string())
+}
+
+//按路径处理请求的函数, 测试时可以替换
+type Route = fn(&State, &Method, &str, &str) -> Result<Value, (u16, String)>;
+
+//处理一个请求, 返回状态码和 JSON 响应. panic 时返回 500, 线程可以继续处理之后的请求
+fn handle(route: Route, state: &State, method: &Method, path: &str, body: &str) -> (u16, Value) {
+    match catch_unwind(AssertUnwindSafe(|| route(state, method, path, body))) {
+        Ok(Ok(v)) => (200, v),
+        Ok(Err((code, msg))) => (code, json!({ "error": msg })),
+        Err(_) => (500, json!({ "error": "internal error" })),
+    }
+}
+
+fn route(state: &State, method: &Method, path: &str, body: &str) -> Result<Value, (u16, String)> {
+    if *method != Method::Post {
+        return Err((405, "only POST is supported".to_string()));
+    }
+    match path {
+        "/cut" => {
+            let req: CutRequest = parse(body)?;
+            let mode = cut_mode(req.mode.as_deref())?;
+            let opts = CutOptions {
+                hmm: req.hmm,
+                ..Default::default()
+            };
+            let jieba = state.jieba.read().map_err(poisoned)?;
+            Ok(json!({ "words": jieba.cut_with(&req.text, mode, opts) }))
+        }
+        "/cut_for_search" => {
+            let req: CutRequest = parse(body)?;
+            let opts = CutOptions {
+                hmm: req.hmm,
+                ..Default::default()
+            };
+            let jieba = state.jieba.read().map_err(poisoned)?;
+            Ok(json!({ "words": jieba.cut_with(&req.text, CutMode::Search, opts) }))
+        }
+        "/tokenize" => {
+            let req: CutRequest = parse(body)?;
+            let mode = match req.mode.as_deref().unwrap_or("default") {
+                "default" => TokenizeMode::Default,
+                "search" => TokenizeMode::Search,
+                m => return Err((400, format!("unknown mode {:?}", m))),
+            };
+            let jieba = state.jieba.read().map_err(poisoned)?;
+            let tokens: Vec<TokenResponse> = jieba
+                .tokenize(&req.text, mode, req.hmm)
+                .into_iter()
+                .map(|t| TokenResponse {
+                    word: t.word,
+                    start: t.start,
+                    end: t.end,
+                    char_start: t.char_start,
+                    char_end: t.char_end,
+                })
+                .collect();
+            Ok(json!({ "tokens": tokens }))
+        }
+        "/extract_tags" => {
+            let req: ExtractRequest = parse(body)?;
+            let allow_pos: Vec<&str> = req.allow_pos.iter().map(|p| p.as_str()).collect();
+            let jieba = state.jieba.read().map_err(poisoned)?;
+            let keywords = match req.method.as_deref().unwrap_or("tfidf") {
+                "tfidf" => state
+                    .tfidf
+                    .extract_tags(&jieba, &req.text, req.top_k, &allow_pos),
+                "textrank" => state
+                    .textrank
+                    .extract_tags(&jieba, &req.text, req.top_k, &allow_pos),
+                m => return Err((400, format!("unknown method {:?}", m))),
+            };
+            Ok(keywords_json(keywords))
+        }
+        "/add_word" => {
+            let req: AddWordRequest = parse(body)?;
+            if req.word.is_empty() {
+                return Err((400, "word is empty".to_string()));
+            }
+            let mut jieba = state.jieba.write().map_err(poisoned)?;
+            let freq = jieba
+                .add_word(&req.word, req.freq, req.tag.as_deref())
+                .map_err(|e| (400, e.to_string()))?;
+            Ok(json!({ "word": req.word, "freq": freq }))
+        }
+        _ => Err((404, format!("not found: {}", path))),
+    }
+}
+
+fn serve(server: Arc<Server>, state: Arc<State>, route: Route) {
+    let content_type = Header::from_bytes("Content-Type", "application/json; charset=utf-8")
+        .expect("valid header");
+    for mut request in server.incoming_requests() {
+        //多读一个字节用于判断是否超出限制, 截断处可能在多字节字符中间, 所以先按字节读入
+        let mut body = Vec::new();
+        let read = request
+            .as_reader()
+            .take(state.max_body + 1)
+            .read_to_end(&mut body);
+        let (code, value) = match read {
+            Ok(n) if n as u64 > state.max_body => (
+                413,
+                json!({ "error": format!("body exceeds {} bytes", state.max_body) }),
+            ),
+            Ok(_) => match std::str::from_utf8(&body) {
+                Ok(body) => {
+                    let path = request.url().split('?').next().unwrap_or_default();
+                    handle(route, &state, request.method(), path, body)
+                }
+                Err(e) => (400, json!({ "error": format!("read body: {}", e) })),
+            },
+            Err(e) => (400, json!({ "error": format!("read body: {}", e) })),
+        };
+        let response = Response::from_string(value.to_string())
+            .with_status_code(code)
+            .with_header(content_type.clone());
+        if let Err(e) = request.respond(response) {
+            eprintln!("jiebars-server: respond: {}", e);
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> Result<(), String> {
+    let mut listen = "127.0.0.1:8000".to_string();
+    let mut threads = 4usize;
+    let mut max_body = DEFAULT_MAX_BODY;
+    let mut builder = JiebaBuilder::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("option {} requires an argument", arg))
+        };
+        match arg.as_str() {
+            "-l" | "--listen" => listen = value()?,
+            "-t" | "--threads" => {
+                threads = value()?
+                    .parse()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or("threads must be a positive integer")?
+            }
+            "-b" | "--max-body" => {
+                max_body = value()?
+                    .parse()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or("max-body must be a positive integer")?
+            }
+            "-D" | "--dict" => builder = builder.dict_path(value()?),
+            "-u" | "--user-dict" => builder = builder.user_dict(value()?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+    let state = Arc::new(State {
+        jieba: RwLock::new(builder.build().map_err(|e| e.to_string())?),
+        tfidf: TfIdf::new().map_err(|e| e.to_string())?,
+        textrank: TextRank::new(),
+        max_body,
+    });
+    let server = Arc::new(Server::http(&listen).map_err(|e| format!("listen {}: {}", listen, e))?);
+    eprintln!("jiebars-server: listening on {}", listen);
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let (server, state) = (server.clone(), state.clone());
+            thread::spawn(move || serve(server, state, route))
+        })
+        .collect();
+    for h in handles {
+        let _ = h.join();
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run(std::env::args().skip(1).collect()) {
+        eprintln!("jiebars-server: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    fn state() -> State {
+        State {
+            jieba: RwLock::new(Jieba::new().unwrap()),
+            tfidf: TfIdf::new().unwrap(),
+            textrank: TextRank::new(),
+            max_body: DEFAULT_MAX_BODY,
+        }
+    }
+
+    #[test]
+    fn test_handle() {
+        let state = state();
+        let post = Method::Post;
+        let (code, v) = handle(
+            route,
+            &state,
+            &post,
+            "/cut",
+            r#"{"text": "他来到了网易杭研大厦", "hmm": false}"#,
+        );
+        assert_eq!(code, 200);
+        assert_eq!(
+            v,
+            json!({"words": ["他", "来到", "了", "网易", "杭", "研", "大厦"]})
+        );
+
+        let (code, v) = handle(
+            route,
+            &state,
+            &post,
+            "/add_word",
+            r#"{"word": "杭研", "tag": "nt"}"#,
+        );
+        assert_eq!(code, 200);
+        assert!(v["freq"].as_f64().unwrap() > 0f64);
+        let (_, v) = handle(
+            route,
+            &state,
+            &post,
+            "/cut",
+            r#"{"text": "他来到了网易杭研大厦", "hmm": false}"#,
+        );
+        assert_eq!(
+            v,
+            json!({"words": ["他", "来到", "了", "网易", "杭研", "大厦"]})
+        );
+
+        for freq in ["-1", "1e999"] {
+            let body = format!(r#"{{"word": "杭研", "freq": {}}}"#, freq);
+            let (code, _) = handle(route, &state, &post, "/add_word", &body);
+            assert_eq!(code, 400);
+        }
+
+        let body = r#"{"text": "他来到了网易杭研大厦", "hmm": false}"#;
+        let (code, v) = handle(route, &state, &post, "/cut_for_search", body);
+        assert_eq!(code, 200);
+        let jieba = state.jieba.read().unwrap();
+        let opts = CutOptions {
+            hmm: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            v,
+            json!({ "words": jieba.cut_with("他来到了网易杭研大厦", CutMode::Search, opts) })
+        );
+        drop(jieba);
+
+        let (code, v) = handle(route, &state, &post, "/tokenize", r#"{"text": "永和服装"}"#);
+        assert_eq!(code, 200);
+        assert_eq!(
+            v["tokens"][1],
+            json!({"word": "服装", "start": 6, "end": 12, "char_start": 2, "char_end": 4})
+        );
+
+        let body =
+            r#"{"text": "我来到北京清华大学, 北京清华大学", "top_k": 2, "method": "textrank"}"#;
+        let (code, v) = handle(route, &state, &post, "/extract_tags", body);
+        assert_eq!(code, 200);
+        assert_eq!(v["keywords"].as_array().unwrap().len(), 2);
+
+        let (code, _) = handle(
+            route,
+            &state,
+            &post,
+            "/cut",
+            r#"{"text": "x", "mode": "bad"}"#,
+        );
+        assert_eq!(code, 400);
+        let (code, _) = handle(route, &state, &post, "/cut", "not json");
+        assert_eq!(code, 400);
+        let (code, _) = handle(route, &state, &post, "/nothing", "{}");
+        assert_eq!(code, 404);
+        let (code, _) = handle(route, &state, &Method::Get, "/cut", "");
+        assert_eq!(code, 405);
+    }
+
+    fn post(addr: std::net::SocketAddr, path: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serve() {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let addr = server.server_addr().to_ip().unwrap();
+        let mut state = state();
+        state.max_body = 64;
+        thread::spawn(move || serve(server, Arc::new(state), route));
+
+        let response = post(addr, "/cut", r#"{"text": "我来到北京清华大学"}"#);
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with(r#"{"words":["我","来到","北京","清华大学"]}"#));
+
+        let body = format!(r#"{{"text": "{}"}}"#, "北京".repeat(20));
+        let response = post(addr, "/cut", &body);
+        assert!(response.starts_with("HTTP/1.1 413"));
+    }
+
+    #[test]
+    fn test_serve_panic() {
+        fn panic_route(
+            state: &State,
+            method: &Method,
+            path: &str,
+            body: &str,
+        ) -> Result<Value, (u16, String)> {
+            if path == "/panic" {
+                panic!("panic in route");
+            }
+            route(state, method, path, body)
+        }
+
+        //只有一个处理线程, panic 之后还能继续处理请求
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let addr = server.server_addr().to_ip().unwrap();
+        let state = Arc::new(state());
+        thread::spawn(move || serve(server, state, panic_route));
+
+        let response = post(addr, "/panic", "{}");
+        assert!(response.starts_with("HTTP/1.1 500"));
+        let response = post(addr, "/cut", r#"{"text": "我来到北京清华大学"}"#);
+        assert!(response.starts_with("HTTP/1.1 200"));
+    }
+}
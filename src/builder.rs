@@ -0,0 +1,327 @@
+use crate::compiled::{CompiledDict, DictBytes};
+use crate::dictionary::Dictionary;
+use crate::error::{Error, JResult};
+use crate::hmm_model::{HmmModel, DEFAULT_HMM_MODEL};
+use crate::oov::{HmmSegmenter, OovSegmenter};
+use crate::posseg::PosModel;
+use crate::segment::{han_detail_regex, han_regex, RE_HAN_DEFAULT, RE_HAN_DETAIL_DEFAULT};
+use crate::Jieba;
+use regex::Regex;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+//主词典来源
+enum DictSource {
+    Default,
+    Empty,
+    Path(PathBuf),
+    Reader(Box<dyn BufRead>),
+    Compiled(PathBuf),
+}
+
+//用户词典来源, 按加入的顺序依次加载
+enum UserDictSource {
+    Path(PathBuf),
+    Reader(Box<dyn BufRead>),
+}
+
+// 构建 Jieba, 默认与 Jieba::new() 相同: 内置词典, 输出非词语部分
+//
+// let jieba = JiebaBuilder::new()
+//     .dict_path("dict.txt")
+//     .user_dict("user.txt")
+//     .keep_non_words(false)
+//     .build()?;
+pub struct JiebaBuilder {
+    dict: DictSource,
+    user_dicts: Vec<UserDictSource>,
+    pos_model: Option<PosModel>,
+    pos_model_path: Option<PathBuf>,
+    hmm_model: Option<HmmModel>,
+    hmm_model_path: Option<PathBuf>,
+    oov_segmenter: Option<Arc<dyn OovSegmenter>>,
+    han_regex: Option<String>,
+    han_ranges: Option<Vec<RangeInclusive<char>>>,
+    keep_non_words: bool,
+}
+
+impl Default for JiebaBuilder {
+    fn default() -> Self {
+        JiebaBuilder::new()
+    }
+}
+
+impl JiebaBuilder {
+    pub fn new() -> JiebaBuilder {
+        JiebaBuilder {
+            dict: DictSource::Default,
+            user_dicts: Vec::new(),
+            pos_model: None,
+            pos_model_path: None,
+            hmm_model: None,
+            hmm_model_path: None,
+            oov_segmenter: None,
+            han_regex: None,
+            han_ranges: None,
+            keep_non_words: true,
+        }
+    }
+
+    //使用内置词典
+    pub fn default_dict(mut self) -> Self {
+        self.dict = DictSource::Default;
+        self
+    }
+
+    //不使用主词典, 只使用用户词典和 HMM
+    pub fn empty_dict(mut self) -> Self {
+        self.dict = DictSource::Empty;
+        self
+    }
+
+    //从文本词典文件加载主词典, 每行格式为 `word freq [tag]`
+    pub fn dict_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.dict = DictSource::Path(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn dict_reader<R: BufRead + 'static>(mut self, reader: R) -> Self {
+        self.dict = DictSource::Reader(Box::new(reader));
+        self
+    }
+
+    //使用 Jieba::save_compiled_dict 生成的预编译词典
+    pub fn compiled_dict<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.dict = DictSource::Compiled(path.as_ref().to_path_buf());
+        self
+    }
+
+    //在主词典之上加载用户词典, 可以调用多次, 后加载的词覆盖先加载的词
+    pub fn user_dict<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.user_dicts
+            .push(UserDictSource::Path(path.as_ref().to_path_buf()));
+        self
+    }
+
+    pub fn user_dict_reader<R: BufRead + 'static>(mut self, reader: R) -> Self {
+        self.user_dicts
+            .push(UserDictSource::Reader(Box::new(reader)));
+        self
+    }
+
+    //词性标注使用的 HMM
+    pub fn pos_model(mut self, model: PosModel) -> Self {
+        self.pos_model = Some(model);
+        self.pos_model_path = None;
+        self
+    }
+
+    pub fn pos_model_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.pos_model_path = Some(path.as_ref().to_path_buf());
+        self.pos_model = None;
+        self
+    }
+
+    //识别未登录词使用的 HMM, 默认使用内置模型
+    pub fn hmm_model(mut self, model: HmmModel) -> Self {
+        self.hmm_model = Some(model);
+        self.hmm_model_path = None;
+        self
+    }
+
+    //从 HmmModel::save 格式的文本文件加载 HMM
+    pub fn hmm_model_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.hmm_model_path = Some(path.as_ref().to_path_buf());
+        self.hmm_model = None;
+        self
+    }
+
+    //自定义的未登录词识别, 设置后 hmm_model 和 hmm_model_path 不再生效
+    pub fn oov_segmenter<S: OovSegmenter + 'static>(mut self, segmenter: S) -> Self {
+        self.oov_segmenter = Some(Arc::new(segmenter));
+        self
+    }
+
+    // 汉字的范围, 默认为 DEFAULT_HAN_RANGES, 包括 CJK 统一汉字的所有扩展区
+    // 使用 LEGACY_HAN_RANGES 可以得到与 jieba 相同的结果
+    pub fn han_ranges(mut self, ranges: &[RangeInclusive<char>]) -> Self {
+        self.han_ranges = Some(ranges.to_vec());
+        self
+    }
+
+    // 匹配需要分词的文本的正则, 其余部分作为非词语处理, 设置后覆盖 han_ranges 生成的正则
+    // 默认为 [汉字a-zA-Z0-9+#&\._%\-]+
+    pub fn han_regex(mut self, pattern: &str) -> Self {
+        self.han_regex = Some(pattern.to_string());
+        self
+    }
+
+    //是否输出标点、空白、emoji 等非词语部分
+    pub fn keep_non_words(mut self, keep: bool) -> Self {
+        self.keep_non_words = keep;
+        self
+    }
+
+    pub fn build(self) -> JResult<Jieba> {
+        let (re_han_default, re_han_detail) = match &self.han_ranges {
+            Some(ranges) => {
+                if ranges.is_empty() {
+                    return Err(Error::InvalidOption("han ranges is empty".to_string()));
+                }
+                if let Some(r) = ranges.iter().find(|r| r.is_empty()) {
+                    return Err(Error::InvalidOption(format!("invalid han range {:?}", r)));
+                }
+                (han_regex(ranges), han_detail_regex(ranges))
+            }
+            None => (RE_HAN_DEFAULT.clone(), RE_HAN_DETAIL_DEFAULT.clone()),
+        };
+        let re_han = match &self.han_regex {
+            Some(pattern) => {
+                let re = Regex::new(pattern)
+                    .map_err(|e| Error::InvalidOption(format!("han regex {:?}: {}", pattern, e)))?;
+                if re.is_match("") {
+                    return Err(Error::InvalidOption(format!(
+                        "han regex {:?} matches empty string",
+                        pattern
+                    )));
+                }
+                re
+            }
+            None => re_han_default,
+        };
+        let pos_model = match (self.pos_model_path, self.pos_model) {
+            (Some(path), _) => Some(PosModel::load(path)?),
+            (None, model) => model,
+        };
+        let oov: Arc<dyn OovSegmenter> = match self.oov_segmenter {
+            Some(segmenter) => segmenter,
+            None => {
+                let model = match (self.hmm_model_path, self.hmm_model) {
+                    (Some(path), _) => Arc::new(HmmModel::load(path)?),
+                    (None, Some(model)) => Arc::new(model),
+                    (None, None) => DEFAULT_HMM_MODEL.clone(),
+                };
+                Arc::new(HmmSegmenter::with_regex(model, re_han_detail.clone()))
+            }
+        };
+        let dict = match self.dict {
+            DictSource::Default => Dictionary::load()?,
+            DictSource::Empty => Dictionary::empty(),
+            DictSource::Path(path) => Dictionary::from_reader(BufReader::new(open(&path)?))?,
+            DictSource::Reader(reader) => Dictionary::from_reader(reader)?,
+            DictSource::Compiled(path) => {
+                Dictionary::from_compiled(CompiledDict::open(DictBytes::open(path)?)?)
+            }
+        };
+        let mut jieba = Jieba {
+            dict,
+            keep_non_words: self.keep_non_words,
+            pos_model,
+            oov,
+            re_han,
+            re_han_detail,
+        };
+        for user_dict in self.user_dicts {
+            match user_dict {
+                UserDictSource::Path(path) => jieba.load_user_dict(path)?,
+                UserDictSource::Reader(reader) => jieba.load_user_dict_from_reader(reader)?,
+            }
+        }
+        Ok(jieba)
+    }
+}
+
+fn open(path: &Path) -> JResult<File> {
+    File::open(path).map_err(|e| Error::UnexpectIO(format!("open {}", path.display()), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_default() {
+        let jieba = JiebaBuilder::new().build().unwrap();
+        let words = jieba.cut("我来到北京清华大学", false, true);
+        assert_eq!(words, vec!["我", "来到", "北京", "清华大学"]);
+    }
+
+    #[test]
+    fn test_build_dict_reader() {
+        let jieba = JiebaBuilder::new()
+            .dict_reader("北京 100 ns\n清华 50 nt\n".as_bytes())
+            .user_dict_reader("大学 20\n".as_bytes())
+            .user_dict_reader("清华大学 nt\n".as_bytes())
+            .keep_non_words(false)
+            .build()
+            .unwrap();
+        let words = jieba.cut("北京, 清华大学", false, false);
+        assert_eq!(words, vec!["北京", "清华大学"]);
+        assert_eq!(jieba.tag("北京"), Some("ns"));
+        assert_eq!(jieba.tag("清华大学"), Some("nt"));
+    }
+
+    #[test]
+    fn test_build_empty_dict() {
+        let jieba = JiebaBuilder::new()
+            .empty_dict()
+            .user_dict_reader("杭研 10\n".as_bytes())
+            .build()
+            .unwrap();
+        let words = jieba.cut("网易杭研", false, false);
+        assert_eq!(words, vec!["网", "易", "杭研"]);
+    }
+
+    #[test]
+    fn test_build_han_regex() {
+        let jieba = JiebaBuilder::new().han_regex(r"[一-鿕]+").build().unwrap();
+        let words = jieba.cut("北京abc", false, true);
+        assert_eq!(words, vec!["北京", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_build_han_ranges() {
+        //䶮 在扩展 A 区, 鿿 是 Unicode 14 加入的汉字
+        let text = "刘䶮称帝鿿";
+        let jieba = JiebaBuilder::new().keep_non_words(false).build().unwrap();
+        assert_eq!(jieba.cut(text, false, true).concat(), text);
+
+        let jieba = JiebaBuilder::new()
+            .han_ranges(crate::LEGACY_HAN_RANGES)
+            .keep_non_words(false)
+            .build()
+            .unwrap();
+        assert_eq!(jieba.cut(text, false, true).concat(), "刘称帝");
+
+        let err = JiebaBuilder::new().han_ranges(&[]).build();
+        assert!(matches!(err, Err(Error::InvalidOption(_))));
+        #[allow(clippy::reversed_empty_ranges)]
+        let err = JiebaBuilder::new().han_ranges(&['龥'..='一']).build();
+        assert!(matches!(err, Err(Error::InvalidOption(_))));
+    }
+
+    #[test]
+    fn test_build_invalid() {
+        let err = JiebaBuilder::new().han_regex("[").build();
+        assert!(matches!(err, Err(Error::InvalidOption(_))));
+        let err = JiebaBuilder::new().han_regex("a*").build();
+        assert!(matches!(err, Err(Error::InvalidOption(_))));
+        let err = JiebaBuilder::new().dict_reader("北京\n".as_bytes()).build();
+        assert!(err.is_err());
+        let err = JiebaBuilder::new().dict_path("no/such/dict.txt").build();
+        assert!(matches!(err, Err(Error::UnexpectIO(_, _))));
+        let err = JiebaBuilder::new()
+            .empty_dict()
+            .user_dict("no/such/user.txt")
+            .build();
+        assert!(err.is_err());
+        let err = JiebaBuilder::new()
+            .empty_dict()
+            .hmm_model_path("no/such/hmm.txt")
+            .build();
+        assert!(matches!(err, Err(Error::UnexpectIO(_, _))));
+    }
+}
@@ -0,0 +1,453 @@
+use crate::error::{Error, JResult};
+use std::convert::TryInto;
+use std::io::Write;
+use std::path::Path;
+
+// 预编译的二进制词典, 所有数值均为小端序, 打开时校验文件头、校验和以及各段的偏移, 查询时直接读取字节
+//
+//   0  magic           8 字节 "JIEBADIC"
+//   8  version         u32
+//  12  reserved        u32
+//  16  checksum        u64  第 32 字节之后所有内容的 FNV-1a 64
+//  24  source_hash     u64  生成该文件的文本词典的 FNV-1a 64
+//  32  total           f64
+//  40  node_count      u32
+//  44  edge_count      u32
+//  48  tag_count       u32
+//  52  tag_blob_len    u32
+//  56  split_blob_len  u32
+//  60  reserved        u32
+//  64  freqs           f64 * node_count
+//      tags            u32 * node_count
+//      child_start     u32 * (node_count + 1)  每个节点的子节点在 edge 数组中的范围, 按字排序
+//      edge_chars      u32 * edge_count
+//      edge_targets    u32 * edge_count
+//      tag_offsets     u32 * (tag_count + 1)
+//      tag_blob        词性名称
+//      split_blob      被删除的词, 以 '\n' 分隔
+const MAGIC: &[u8; 8] = b"JIEBADIC";
+pub(crate) const VERSION: u32 = 1;
+const HEADER_LEN: usize = 64;
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+pub(crate) enum DictBytes {
+    Static(&'static [u8]),
+    #[cfg_attr(all(feature = "mmap", not(test)), allow(dead_code))]
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mmap(memmap2::Mmap),
+}
+
+impl DictBytes {
+    //开启 mmap 特性时映射文件, 否则一次性读入内存
+    pub(crate) fn open<P: AsRef<Path>>(path: P) -> JResult<DictBytes> {
+        let path = path.as_ref();
+        #[cfg(feature = "mmap")]
+        {
+            let file = std::fs::File::open(path)
+                .map_err(|e| Error::UnexpectIO(format!("open {}", path.display()), e))?;
+            // SAFETY: 词典文件以只读方式映射, 调用方需保证使用期间文件不被修改
+            let mmap = unsafe { memmap2::Mmap::map(&file) }
+                .map_err(|e| Error::UnexpectIO(format!("mmap {}", path.display()), e))?;
+            Ok(DictBytes::Mmap(mmap))
+        }
+        #[cfg(not(feature = "mmap"))]
+        {
+            let data = std::fs::read(path)
+                .map_err(|e| Error::UnexpectIO(format!("read {}", path.display()), e))?;
+            Ok(DictBytes::Owned(data))
+        }
+    }
+}
+
+impl AsRef<[u8]> for DictBytes {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            DictBytes::Static(b) => b,
+            DictBytes::Owned(b) => b,
+            #[cfg(feature = "mmap")]
+            DictBytes::Mmap(m) => m,
+        }
+    }
+}
+
+pub(crate) struct CompiledDict {
+    data: DictBytes,
+    pub total: f64,
+    pub source_hash: u64,
+    pub node_count: u32,
+    tag_count: usize,
+    freqs_off: usize,
+    tags_off: usize,
+    child_start_off: usize,
+    edge_chars_off: usize,
+    edge_targets_off: usize,
+    tag_offsets_off: usize,
+    tag_blob_off: usize,
+    split_blob_off: usize,
+    end: usize,
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::InvalidCompiledDict(msg.to_string())
+}
+
+impl CompiledDict {
+    pub(crate) fn open(data: DictBytes) -> JResult<CompiledDict> {
+        let bytes = data.as_ref();
+        if bytes.len() < HEADER_LEN || &bytes[..8] != MAGIC {
+            return Err(invalid("bad magic"));
+        }
+        let version = read_u32(bytes, 8);
+        if version != VERSION {
+            return Err(invalid(&format!("unsupported version {}", version)));
+        }
+        if read_u64(bytes, 16) != fnv1a(&bytes[32..]) {
+            return Err(invalid("checksum mismatch"));
+        }
+        let node_count = read_u32(bytes, 40) as usize;
+        let edge_count = read_u32(bytes, 44) as usize;
+        let tag_count = read_u32(bytes, 48) as usize;
+        let tag_blob_len = read_u32(bytes, 52) as usize;
+        //各段的长度都来自文件, 用 u64 计算偏移, 在 32 位平台上也不会溢出
+        let section = |off: u64, len: usize, width: u64| off + len as u64 * width;
+        let tags_off = section(HEADER_LEN as u64, node_count, 8);
+        let child_start_off = section(tags_off, node_count, 4);
+        let edge_chars_off = section(child_start_off, node_count, 4) + 4;
+        let edge_targets_off = section(edge_chars_off, edge_count, 4);
+        let tag_offsets_off = section(edge_targets_off, edge_count, 4);
+        let tag_blob_off = section(tag_offsets_off, tag_count, 4) + 4;
+        let split_blob_off = section(tag_blob_off, tag_blob_len, 1);
+        let end = section(split_blob_off, read_u32(bytes, 56) as usize, 1);
+        if node_count == 0 || end != bytes.len() as u64 {
+            return Err(invalid("truncated"));
+        }
+        let freqs_off = HEADER_LEN;
+        let tags_off = tags_off as usize;
+        let child_start_off = child_start_off as usize;
+        let edge_chars_off = edge_chars_off as usize;
+        let edge_targets_off = edge_targets_off as usize;
+        let tag_offsets_off = tag_offsets_off as usize;
+        let tag_blob_off = tag_blob_off as usize;
+        let split_blob_off = split_blob_off as usize;
+        let end = end as usize;
+        //查询时不再检查下标, 打开时校验所有子节点范围、子节点编号和词性名称的偏移
+        let mut prev = 0;
+        for i in 0..=node_count {
+            let start = read_u32(bytes, child_start_off + 4 * i) as usize;
+            if start < prev || start > edge_count {
+                return Err(invalid("bad child range"));
+            }
+            prev = start;
+        }
+        if prev != edge_count {
+            return Err(invalid("bad child range"));
+        }
+        for i in 0..edge_count {
+            if read_u32(bytes, edge_targets_off + 4 * i) as usize >= node_count {
+                return Err(invalid("bad edge target"));
+            }
+        }
+        let mut prev = 0;
+        for i in 0..=tag_count {
+            let offset = read_u32(bytes, tag_offsets_off + 4 * i) as usize;
+            if offset < prev || offset > tag_blob_len {
+                return Err(invalid("bad tag offset"));
+            }
+            prev = offset;
+        }
+        Ok(CompiledDict {
+            total: f64::from_le_bytes(bytes[32..40].try_into().unwrap()),
+            source_hash: read_u64(bytes, 24),
+            node_count: node_count as u32,
+            tag_count,
+            freqs_off,
+            tags_off,
+            child_start_off,
+            edge_chars_off,
+            edge_targets_off,
+            tag_offsets_off,
+            tag_blob_off,
+            split_blob_off,
+            end,
+            data,
+        })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+
+    pub(crate) fn freq(&self, node: u32) -> f64 {
+        let off = self.freqs_off + 8 * node as usize;
+        f64::from_le_bytes(self.bytes()[off..off + 8].try_into().unwrap())
+    }
+
+    pub(crate) fn tag(&self, node: u32) -> u32 {
+        read_u32(self.bytes(), self.tags_off + 4 * node as usize)
+    }
+
+    fn child_range(&self, node: u32) -> (usize, usize) {
+        let off = self.child_start_off + 4 * node as usize;
+        let start = read_u32(self.bytes(), off) as usize;
+        let end = read_u32(self.bytes(), off + 4) as usize;
+        (start, end)
+    }
+
+    //在子节点中二分查找字 c
+    pub(crate) fn child(&self, node: u32, c: char) -> Option<u32> {
+        let (mut lo, mut hi) = self.child_range(node);
+        let bytes = self.bytes();
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let mc = read_u32(bytes, self.edge_chars_off + 4 * mid);
+            match mc.cmp(&(c as u32)) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => {
+                    return Some(read_u32(bytes, self.edge_targets_off + 4 * mid))
+                }
+            }
+        }
+        None
+    }
+
+    pub(crate) fn children(&self, node: u32) -> impl Iterator<Item = (char, u32)> + '_ {
+        let (start, end) = self.child_range(node);
+        (start..end).filter_map(move |i| {
+            let c = char::from_u32(read_u32(self.bytes(), self.edge_chars_off + 4 * i))?;
+            Some((c, read_u32(self.bytes(), self.edge_targets_off + 4 * i)))
+        })
+    }
+
+    pub(crate) fn tag_names(&self) -> Vec<String> {
+        let bytes = self.bytes();
+        (0..self.tag_count)
+            .map(|i| {
+                let start = read_u32(bytes, self.tag_offsets_off + 4 * i) as usize;
+                let end = read_u32(bytes, self.tag_offsets_off + 4 * (i + 1)) as usize;
+                let blob = &bytes[self.tag_blob_off..self.split_blob_off];
+                String::from_utf8_lossy(&blob[start..end]).into_owned()
+            })
+            .collect()
+    }
+
+    pub(crate) fn force_split(&self) -> impl Iterator<Item = &str> {
+        std::str::from_utf8(&self.bytes()[self.split_blob_off..self.end])
+            .unwrap_or("")
+            .split('\n')
+            .filter(|w| !w.is_empty())
+    }
+}
+
+// 先写入同一目录下的临时文件再改名, 避免其他进程映射的旧文件被截断 (mmap 会因此收到 SIGBUS)
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> JResult<()> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.tmp", std::process::id()));
+    let tmp = path.with_file_name(name);
+    let result = std::fs::File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&tmp, path));
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(Error::UnexpectIO(format!("write {}", path.display()), e));
+    }
+    Ok(())
+}
+
+fn read_u32(bytes: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(bytes[off..off + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], off: usize) -> u64 {
+    u64::from_le_bytes(bytes[off..off + 8].try_into().unwrap())
+}
+
+//按广度优先顺序编号后的一个节点
+pub(crate) struct CompiledNode {
+    pub freq: f64,
+    pub tag: u32,
+    //按字排序的子节点
+    pub children: Vec<(char, u32)>,
+}
+
+pub(crate) fn encode(
+    nodes: &[CompiledNode],
+    tag_names: &[String],
+    force_split: &[&str],
+    total: f64,
+    source_hash: u64,
+) -> Vec<u8> {
+    let edge_count: usize = nodes.iter().map(|n| n.children.len()).sum();
+    let tag_blob: Vec<u8> = tag_names.iter().flat_map(|t| t.bytes()).collect();
+    let split_blob = force_split.join("\n").into_bytes();
+
+    let mut out: Vec<u8> = Vec::with_capacity(HEADER_LEN + nodes.len() * 20 + edge_count * 8);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&0u64.to_le_bytes());
+    out.extend_from_slice(&source_hash.to_le_bytes());
+    out.extend_from_slice(&total.to_le_bytes());
+    for n in [
+        nodes.len(),
+        edge_count,
+        tag_names.len(),
+        tag_blob.len(),
+        split_blob.len(),
+        0,
+    ] {
+        out.extend_from_slice(&(n as u32).to_le_bytes());
+    }
+    for n in nodes {
+        out.extend_from_slice(&n.freq.to_le_bytes());
+    }
+    for n in nodes {
+        out.extend_from_slice(&n.tag.to_le_bytes());
+    }
+    let mut start = 0u32;
+    for n in nodes {
+        out.extend_from_slice(&start.to_le_bytes());
+        start += n.children.len() as u32;
+    }
+    out.extend_from_slice(&start.to_le_bytes());
+    for n in nodes {
+        for (c, _) in n.children.iter() {
+            out.extend_from_slice(&(*c as u32).to_le_bytes());
+        }
+    }
+    for n in nodes {
+        for (_, child) in n.children.iter() {
+            out.extend_from_slice(&child.to_le_bytes());
+        }
+    }
+    let mut offset = 0u32;
+    out.extend_from_slice(&offset.to_le_bytes());
+    for t in tag_names {
+        offset += t.len() as u32;
+        out.extend_from_slice(&offset.to_le_bytes());
+    }
+    out.extend_from_slice(&tag_blob);
+    out.extend_from_slice(&split_blob);
+    let checksum = fnv1a(&out[32..]);
+    out[16..24].copy_from_slice(&checksum.to_le_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<u8> {
+        //根 -> 北 -> 京, 根 -> 南
+        let nodes = [
+            CompiledNode {
+                freq: 0f64,
+                tag: u32::MAX,
+                children: vec![('北', 1), ('南', 2)],
+            },
+            CompiledNode {
+                freq: 0f64,
+                tag: u32::MAX,
+                children: vec![('京', 3)],
+            },
+            CompiledNode {
+                freq: 5f64,
+                tag: 1,
+                children: vec![],
+            },
+            CompiledNode {
+                freq: 10f64,
+                tag: 0,
+                children: vec![],
+            },
+        ];
+        encode(
+            &nodes,
+            &["ns".to_string(), "f".to_string()],
+            &["南京"],
+            15f64,
+            42,
+        )
+    }
+
+    #[test]
+    fn test_encode_open() {
+        let dict = CompiledDict::open(DictBytes::Owned(sample())).unwrap();
+        assert_eq!(dict.node_count, 4);
+        assert_eq!(dict.total, 15f64);
+        assert_eq!(dict.source_hash, 42);
+        let bei = dict.child(0, '北').unwrap();
+        let jing = dict.child(bei, '京').unwrap();
+        assert_eq!(dict.freq(jing), 10f64);
+        assert_eq!(dict.tag_names()[dict.tag(jing) as usize], "ns");
+        assert_eq!(dict.child(0, '京'), None);
+        assert_eq!(dict.children(0).count(), 2);
+        assert_eq!(dict.force_split().collect::<Vec<&str>>(), vec!["南京"]);
+    }
+
+    #[test]
+    fn test_open_invalid() {
+        let mut data = sample();
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        assert!(CompiledDict::open(DictBytes::Owned(data)).is_err());
+        let mut data = sample();
+        data[8] = 2;
+        assert!(CompiledDict::open(DictBytes::Owned(data)).is_err());
+        assert!(CompiledDict::open(DictBytes::Static(b"JIEBADIC")).is_err());
+    }
+
+    //校验和正确但偏移越界的文件也不能 panic
+    fn reseal(mut data: Vec<u8>) -> Vec<u8> {
+        let checksum = fnv1a(&data[32..]);
+        data[16..24].copy_from_slice(&checksum.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_open_bad_offsets() {
+        let edge_targets_off = HEADER_LEN + 8 * 4 + 4 * 4 + 4 * 5 + 4 * 3;
+        let mut data = sample();
+        data[edge_targets_off..edge_targets_off + 4].copy_from_slice(&9u32.to_le_bytes());
+        assert!(CompiledDict::open(DictBytes::Owned(reseal(data))).is_err());
+
+        let child_start_off = HEADER_LEN + 8 * 4 + 4 * 4;
+        let mut data = sample();
+        data[child_start_off + 4..child_start_off + 8].copy_from_slice(&7u32.to_le_bytes());
+        assert!(CompiledDict::open(DictBytes::Owned(reseal(data))).is_err());
+
+        let tag_offsets_off = edge_targets_off + 4 * 3;
+        let mut data = sample();
+        data[tag_offsets_off + 4..tag_offsets_off + 8].copy_from_slice(&100u32.to_le_bytes());
+        assert!(CompiledDict::open(DictBytes::Owned(reseal(data))).is_err());
+
+        let mut data = sample();
+        data[40..44].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(CompiledDict::open(DictBytes::Owned(reseal(data))).is_err());
+
+        assert!(CompiledDict::open(DictBytes::Owned(reseal(sample()))).is_ok());
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let path = std::env::temp_dir().join(format!("jiebars-atomic-{}.dict", std::process::id()));
+        write_atomic(&path, b"old").unwrap();
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        std::fs::remove_file(&path).unwrap();
+        let missing = std::env::temp_dir().join("jiebars-no-such-dir/x.dict");
+        assert!(write_atomic(&missing, b"data").is_err());
+    }
+}
@@ -1,70 +1,331 @@
-use crate::error::JResult;
-use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, Lines};
-use std::path::Path;
+use crate::compiled::{encode, fnv1a, CompiledDict, CompiledNode, VERSION};
+use crate::error::{Error, JResult};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader};
 
 //把数据文件读进内存
 static DEFAULT_DICT: &str = include_str!("data/dict.txt");
 
+// 词典中没有标注词性的词使用的词性
+pub(crate) const DEFAULT_TAG: &str = "x";
+
+// 词典中的一行, 格式为 `word [freq] [tag]`, 词频和词性可以省略
+pub(crate) struct DictEntry {
+    pub word: String,
+    pub freq: Option<f64>,
+    pub tag: Option<String>,
+}
+
+// 解析整个词典, 任意一行格式错误则返回错误
+pub(crate) fn parse_dict<R: BufRead>(reader: R) -> JResult<Vec<DictEntry>> {
+    let mut entries: Vec<DictEntry> = Vec::new();
+    for (i, res_line) in reader.lines().enumerate() {
+        let line = res_line?;
+        let line = if i == 0 {
+            line.trim_start_matches('\u{feff}')
+        } else {
+            line.as_str()
+        };
+        let elem = line.split_whitespace().collect::<Vec<&str>>();
+        if elem.is_empty() {
+            continue;
+        }
+        // 两列时第二列既可以是词频也可以是词性, 与 jieba 保持一致
+        let (freq, tag) = match elem[1..] {
+            [] => (None, None),
+            [f] => match f.parse::<f64>() {
+                Ok(f) if f.is_finite() && f >= 0f64 => (Some(f), None),
+                Err(_) if f.chars().all(|c| c.is_ascii_alphabetic()) => (None, Some(f)),
+                _ => return Err(Error::InvalidDictEntry(i + 1, line.to_string())),
+            },
+            [f, t] => match f.parse::<f64>() {
+                Ok(f) if f.is_finite() && f >= 0f64 => (Some(f), Some(t)),
+                _ => return Err(Error::InvalidDictEntry(i + 1, line.to_string())),
+            },
+            _ => return Err(Error::InvalidDictEntry(i + 1, line.to_string())),
+        };
+        entries.push(DictEntry {
+            word: elem[0].to_string(),
+            freq,
+            tag: tag.map(|t| t.to_string()),
+        });
+    }
+    Ok(entries)
+}
+
+//trie 的根节点
+const ROOT: u32 = 0;
+//没有词性的节点
+const NO_TAG: u32 = u32::MAX;
+
+// 用 trie 保存词典, 每个节点对应一个词或词的前缀
+// 边保存在一张 (父节点, 字) -> 子节点 的哈希表中, 查找一个字只需要一次定长 key 的哈希,
+// 构建 DAG 时从每个位置向后逐字查找, 遇到不存在的前缀即可停止
+//
+// 从预编译词典打开时, 预编译词典中的节点只读, 编号为 0..base_len,
+// 之后加入的节点和对预编译节点的修改都记录在哈希表中
 pub(crate) struct Dictionary {
-    dict: HashMap<String, f64>,
-    total: f64,
+    base: Option<CompiledDict>,
+    base_len: u32,
+    //对预编译词典中节点的修改, (词频, 词性)
+    base_changes: HashMap<u32, (f64, u32)>,
+    edges: HashMap<(u32, char), u32>,
+    //每个节点的词频, 只是前缀的节点词频为 0, 下标为 node - base_len
+    freqs: Vec<f64>,
+    //每个节点的词性在 tag_names 中的下标, 下标为 node - base_len
+    tags: Vec<u32>,
+    tag_names: Vec<String>,
+    tag_index: HashMap<String, u32>,
+    //被删除的词, HMM 切出这些词时需要拆成单字
+    force_split: HashSet<String>,
+    //生成该词典的文本词典的哈希, 用于判断预编译词典是否过期
+    pub source_hash: u64,
+    pub total: f64,
     pub log_total: f64,
 }
 
 impl Dictionary {
-    pub(crate) fn load() -> JResult<Dictionary> {
-        let lines = BufReader::new(DEFAULT_DICT.as_bytes()).lines();
-        let mut db = Dictionary {
-            dict: HashMap::new(),
+    pub(crate) fn empty() -> Dictionary {
+        Dictionary {
+            base: None,
+            base_len: 0,
+            base_changes: HashMap::new(),
+            edges: HashMap::new(),
+            freqs: vec![0f64],
+            tags: vec![NO_TAG],
+            tag_names: Vec::new(),
+            tag_index: HashMap::new(),
+            force_split: HashSet::new(),
+            source_hash: 0,
             total: 0f64,
             log_total: 0f64,
-        };
-        db.add_word(lines);
-        db.log_total = db.total.ln();
+        }
+    }
+
+    //内置词典的所有词
+    pub(crate) fn default_entries() -> JResult<Vec<DictEntry>> {
+        parse_dict(DEFAULT_DICT.as_bytes())
+    }
+
+    pub(crate) fn load() -> JResult<Dictionary> {
+        let mut db = Dictionary::from_reader(BufReader::new(DEFAULT_DICT.as_bytes()))?;
+        db.source_hash = default_source_hash();
         Ok(db)
     }
 
-    pub(crate) fn add_word(&mut self, lines: Lines<BufReader<&[u8]>>) {
-        for res_line in lines {
-            if let Ok(line) = res_line {
-                let elem = line.split_whitespace().collect::<Vec<&str>>();
-                if elem.len() != 3 {
-                    continue;
-                }
-                let u = match elem[1].parse::<f64>() {
-                    Ok(u) => u,
-                    Err(_e) => continue,
-                };
-                self.total += u;
-                self.dict.insert(elem[0].to_string(), u);
-                let cs = elem[0].chars().collect::<Vec<char>>();
-                for i in 0..cs.len() {
-                    let c = cs[..i + 1].iter().collect();
-                    if self.dict.get(&c).is_none() {
-                        self.dict.insert(c, 0f64);
-                    }
-
-                    // self.dict
-                    //     .entry(cs[..i + 1].iter().collect())
-                    //     .or_insert(0f64);
-                }
+    //从文本词典加载, 主词典的每一行都必须有词频
+    pub(crate) fn from_reader<R: BufRead>(reader: R) -> JResult<Dictionary> {
+        let mut db = Dictionary::empty();
+        for entry in parse_dict(reader)? {
+            let freq = entry.freq.ok_or_else(|| {
+                Error::Unexpected(format!("missing frequency of {} in dictionary", entry.word))
+            })?;
+            db.add_word(&entry.word, freq, entry.tag.as_deref());
+        }
+        Ok(db)
+    }
+
+    //打开预编译词典, 不需要解析
+    pub(crate) fn from_compiled(base: CompiledDict) -> Dictionary {
+        let mut db = Dictionary::empty();
+        db.freqs.clear();
+        db.tags.clear();
+        for (id, name) in base.tag_names().into_iter().enumerate() {
+            db.tag_index.entry(name.clone()).or_insert(id as u32);
+            db.tag_names.push(name);
+        }
+        db.force_split = base.force_split().map(|w| w.to_string()).collect();
+        db.base_len = base.node_count;
+        db.source_hash = base.source_hash;
+        db.total = base.total;
+        db.log_total = base.total.ln();
+        db.base = Some(base);
+        db
+    }
+
+    //按广度优先顺序重新编号所有节点, 生成预编译词典
+    pub(crate) fn compile(&self) -> Vec<u8> {
+        let mut overlay: HashMap<u32, Vec<(char, u32)>> = HashMap::new();
+        for ((parent, c), child) in self.edges.iter() {
+            overlay.entry(*parent).or_default().push((*c, *child));
+        }
+        let mut order: Vec<u32> = vec![ROOT];
+        let mut ids: HashMap<u32, u32> = HashMap::new();
+        ids.insert(ROOT, 0);
+        let mut nodes: Vec<CompiledNode> = Vec::new();
+        let mut i = 0;
+        while i < order.len() {
+            let node = order[i];
+            let mut children: Vec<(char, u32)> = overlay.remove(&node).unwrap_or_default();
+            if let Some(base) = self.base.as_ref().filter(|_| node < self.base_len) {
+                children.extend(base.children(node));
+            }
+            children.sort_unstable();
+            for (_, child) in children.iter_mut() {
+                let id = order.len() as u32;
+                ids.insert(*child, id);
+                order.push(*child);
+                *child = id;
+            }
+            let (freq, tag) = self.node_value(node);
+            nodes.push(CompiledNode {
+                freq,
+                tag,
+                children,
+            });
+            i += 1;
+        }
+        let force_split: Vec<&str> = self.force_split.iter().map(|w| w.as_str()).collect();
+        encode(
+            &nodes,
+            &self.tag_names,
+            &force_split,
+            self.total,
+            self.source_hash,
+        )
+    }
+
+    fn child(&self, node: u32, c: char) -> Option<u32> {
+        if !self.edges.is_empty() {
+            if let Some(child) = self.edges.get(&(node, c)) {
+                return Some(*child);
             }
         }
+        match &self.base {
+            Some(base) if node < self.base_len => base.child(node, c),
+            _ => None,
+        }
+    }
+
+    //节点的词频和词性
+    fn node_value(&self, node: u32) -> (f64, u32) {
+        match &self.base {
+            Some(base) if node < self.base_len => match self.base_changes.get(&node) {
+                Some(v) => *v,
+                None => (base.freq(node), base.tag(node)),
+            },
+            _ => {
+                let i = (node - self.base_len) as usize;
+                (self.freqs[i], self.tags[i])
+            }
+        }
+    }
+
+    fn set_node_value(&mut self, node: u32, freq: f64, tag: u32) {
+        if node < self.base_len {
+            self.base_changes.insert(node, (freq, tag));
+        } else {
+            let i = (node - self.base_len) as usize;
+            self.freqs[i] = freq;
+            self.tags[i] = tag;
+        }
+    }
+
+    fn node(&self, word: &str) -> Option<u32> {
+        if word.is_empty() {
+            return None;
+        }
+        word.chars().try_fold(ROOT, |node, c| self.child(node, c))
+    }
+
+    //找到词对应的节点, 沿途不存在的前缀节点都会被创建
+    fn insert_node(&mut self, word: &str) -> u32 {
+        let mut node = ROOT;
+        for c in word.chars() {
+            node = match self.child(node, c) {
+                Some(child) => child,
+                None => {
+                    let child = self.base_len + self.freqs.len() as u32;
+                    self.edges.insert((node, c), child);
+                    self.freqs.push(0f64);
+                    self.tags.push(NO_TAG);
+                    child
+                }
+            };
+        }
+        node
+    }
+
+    fn tag_id(&mut self, tag: &str) -> u32 {
+        if let Some(id) = self.tag_index.get(tag) {
+            return *id;
+        }
+        let id = self.tag_names.len() as u32;
+        self.tag_names.push(tag.to_string());
+        self.tag_index.insert(tag.to_string(), id);
+        id
+    }
+
+    // 加入一个词及其所有前缀, 前缀的词频为 0, 已存在的词覆盖原词频
+    // 省略词性时保留已存在的词的词性, 新词使用 DEFAULT_TAG
+    // 词频为 0 的词只作为前缀存在, 相当于被删除
+    pub(crate) fn add_word(&mut self, word: &str, freq: f64, tag: Option<&str>) {
+        if word.is_empty() {
+            return;
+        }
+        if freq > 0f64 {
+            self.force_split.remove(word);
+        } else {
+            self.force_split.insert(word.to_string());
+        }
+        let node = self.insert_node(word);
+        let (old, old_tag) = self.node_value(node);
+        let tag = match tag {
+            Some(tag) => self.tag_id(tag),
+            None if old_tag != NO_TAG => old_tag,
+            None => self.tag_id(DEFAULT_TAG),
+        };
+        self.set_node_value(node, freq, tag);
+        self.total += freq - old;
+        self.log_total = self.total.ln();
+    }
+
+    pub(crate) fn del_word(&mut self, word: &str) {
+        self.add_word(word, 0f64, None);
+        if let Some(node) = self.node(word) {
+            self.set_node_value(node, 0f64, NO_TAG);
+        }
     }
 
+    pub(crate) fn is_force_split(&self, word: &str) -> bool {
+        self.force_split.contains(word)
+    }
+
+    //词频, 只是前缀的词返回 Some(0), 不在词典中的词返回 None
     pub(crate) fn frequency(&self, key: &str) -> Option<f64> {
-        self.dict.get(key).map(|x| *x)
+        self.node(key).map(|node| self.node_value(node).0)
+    }
+
+    pub(crate) fn tag(&self, word: &str) -> Option<&str> {
+        let (_, id) = self.node_value(self.node(word)?);
+        self.tag_names.get(id as usize).map(|t| t.as_str())
+    }
+
+    //sentence 所有在词典中的前缀, 返回前缀的结束位置和词频, 遇到不存在的前缀即停止
+    pub(crate) fn prefixes<'a>(
+        &'a self,
+        sentence: &'a str,
+    ) -> impl Iterator<Item = (usize, f64)> + 'a {
+        let mut node = ROOT;
+        sentence
+            .char_indices()
+            .map_while(move |(i, c)| {
+                node = self.child(node, c)?;
+                Some((i + c.len_utf8(), self.node_value(node).0))
+            })
+            .filter(|(_, freq)| *freq > 0f64)
     }
 }
 
+//默认词典的哈希, 与预编译词典中记录的哈希不同时说明预编译词典已经过期
+pub(crate) fn default_source_hash() -> u64 {
+    fnv1a(DEFAULT_DICT.as_bytes()) ^ (VERSION as u64)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::env;
-    use std::os::unix::prelude::FileExt;
-    use std::str::Chars;
+    use crate::compiled::DictBytes;
     #[test]
     fn test_split_chinese_str() {
         let s = "程序设计艺术";
@@ -80,4 +341,103 @@ mod tests {
             println!("freq:{}", freq);
         }
     }
+
+    #[test]
+    fn test_parse_dict() {
+        let entries =
+            parse_dict("\u{feff}杭研 10 nt\n\n网易杭研大厦 5\n云计算\n大数据 n\n".as_bytes())
+                .unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].word, "杭研");
+        assert_eq!(entries[0].freq, Some(10f64));
+        assert_eq!(entries[0].tag.as_deref(), Some("nt"));
+        assert_eq!(entries[1].freq, Some(5f64));
+        assert_eq!(entries[1].tag, None);
+        assert_eq!(entries[2].freq, None);
+        assert_eq!(entries[2].tag, None);
+        assert_eq!(entries[3].freq, None);
+        assert_eq!(entries[3].tag.as_deref(), Some("n"));
+
+        let err = parse_dict("杭研 10 nt\n杭研大厦 x nt\n".as_bytes())
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::InvalidDictEntry(2, _)));
+        let err = parse_dict("杭研 10 nt extra\n".as_bytes()).err().unwrap();
+        assert!(matches!(err, Error::InvalidDictEntry(1, _)));
+        let err = parse_dict("杭研 -1\n".as_bytes()).err().unwrap();
+        assert!(matches!(err, Error::InvalidDictEntry(1, _)));
+        for line in ["杭研 inf\n", "杭研 inf nt\n", "杭研 NaN nt\n"] {
+            let err = parse_dict(line.as_bytes()).err().unwrap();
+            assert!(matches!(err, Error::InvalidDictEntry(1, _)));
+        }
+    }
+
+    #[test]
+    fn test_add_word() {
+        let mut dict = Dictionary::load().unwrap();
+        let total = dict.total;
+        dict.add_word("网易杭研", 10f64, Some("nt"));
+        assert_eq!(dict.frequency("网易杭研"), Some(10f64));
+        assert_eq!(dict.frequency("网易杭"), Some(0f64));
+        assert_eq!(dict.tag("网易杭研"), Some("nt"));
+        assert_eq!(dict.tag("网易杭"), None);
+        //省略词性时保留原有的词性
+        dict.add_word("网易杭研", 5f64, None);
+        assert_eq!(dict.tag("网易杭研"), Some("nt"));
+        assert_eq!(dict.total, total + 5f64);
+        assert_eq!(dict.log_total, dict.total.ln());
+
+        dict.del_word("网易杭研");
+        assert_eq!(dict.frequency("网易杭研"), Some(0f64));
+        assert_eq!(dict.tag("网易杭研"), None);
+        assert!(dict.is_force_split("网易杭研"));
+        assert!((dict.total - total).abs() < 1e-6);
+        //删除后再加入视为新词
+        dict.add_word("网易杭研", 5f64, None);
+        assert_eq!(dict.tag("网易杭研"), Some(DEFAULT_TAG));
+    }
+
+    #[test]
+    fn test_compile() {
+        let mut dict = Dictionary::load().unwrap();
+        dict.add_word("网易杭研", 10f64, Some("nt"));
+        dict.del_word("清华大学");
+        let data = dict.compile();
+        let mut compiled =
+            Dictionary::from_compiled(CompiledDict::open(DictBytes::Owned(data)).unwrap());
+        for word in ["我们", "网易杭研", "网易杭", "清华大学", "不存在的词"] {
+            assert_eq!(compiled.frequency(word), dict.frequency(word));
+            assert_eq!(compiled.tag(word), dict.tag(word));
+        }
+        assert!(compiled.is_force_split("清华大学"));
+        assert_eq!(compiled.total, dict.total);
+        assert_eq!(compiled.source_hash, default_source_hash());
+
+        compiled.add_word("杭研大厦", 5f64, Some("nt"));
+        compiled.add_word("我们", 1f64, None);
+        assert_eq!(compiled.frequency("杭研大厦"), Some(5f64));
+        assert_eq!(compiled.frequency("杭研"), Some(0f64));
+        assert_eq!(compiled.frequency("我们"), Some(1f64));
+        assert_eq!(compiled.tag("我们"), Some("r"));
+        let prefixes: Vec<usize> = compiled.prefixes("网易杭研大厦").map(|x| x.0).collect();
+        assert_eq!(prefixes, vec![3, 6, 12]);
+
+        let recompiled = Dictionary::from_compiled(
+            CompiledDict::open(DictBytes::Owned(compiled.compile())).unwrap(),
+        );
+        assert_eq!(recompiled.frequency("杭研大厦"), Some(5f64));
+        assert_eq!(recompiled.tag("杭研大厦"), Some("nt"));
+    }
+
+    #[test]
+    fn test_prefixes() {
+        let dict = Dictionary::load().unwrap();
+        let prefixes: Vec<&str> = dict
+            .prefixes("清华大学xyz")
+            .map(|(end, _)| &"清华大学xyz"[..end])
+            .collect();
+        assert_eq!(prefixes, vec!["清", "清华", "清华大学"]);
+        assert_eq!(dict.prefixes("").count(), 0);
+        assert_eq!(dict.frequency(""), None);
+    }
 }
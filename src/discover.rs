@@ -0,0 +1,347 @@
+use crate::error::{Error, JResult};
+use crate::Jieba;
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+// 从未分词的语料中发现新词
+// 统计连续汉字中所有长度不超过 max_len 的 n-gram, 候选词需要满足:
+//   出现次数不少于 min_count
+//   凝固度 (按任意位置拆成两部分的最小点互信息) 不低于 min_pmi
+//   自由度 (左右邻字信息熵的较小值) 不低于 min_entropy
+//   不在词典中
+// 句首句尾没有邻字, 每次出现都当作一个不同的邻字计算信息熵
+// 汉字的范围与创建时传入的 Jieba 相同
+//
+// 每个不同的 n-gram 连同它的邻字表约占 200 字节以上, max_len 为 4 时每个字最多产生 4 个 n-gram.
+// n-gram 数超过 max_ngrams 时删除出现次数较少的多字 n-gram, 直到剩下不到一半,
+// 之后这些 n-gram 从 0 重新计数, 所以语料很大时少见的词会被漏掉
+//
+// let mut discoverer = WordDiscoverer::new(&jieba);
+// discoverer.add_reader(BufReader::new(File::open("corpus.txt")?))?;
+// let words = discoverer.discover(&jieba, 100);
+// write_user_dict(&words, &mut File::create("new_words.txt")?)?;
+// jieba.load_user_dict("new_words.txt")?;
+pub struct WordDiscoverer {
+    re_han: Regex,
+    max_len: usize,
+    min_count: u64,
+    min_pmi: f64,
+    min_entropy: f64,
+    max_ngrams: usize,
+    //已删除出现次数不超过该值的多字 n-gram
+    pruned: u64,
+    total: u64,
+    ngrams: HashMap<String, Ngram>,
+}
+
+#[derive(Default)]
+struct Ngram {
+    count: u64,
+    left: HashMap<char, u64>,
+    right: HashMap<char, u64>,
+    //出现在句首、句尾的次数
+    left_bound: u64,
+    right_bound: u64,
+}
+
+//发现的新词, freq 为能让该词被切分出来的建议词频
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewWord {
+    pub word: String,
+    pub count: u64,
+    pub pmi: f64,
+    pub left_entropy: f64,
+    pub right_entropy: f64,
+    pub score: f64,
+    pub freq: f64,
+}
+
+impl WordDiscoverer {
+    pub fn new(jieba: &Jieba) -> WordDiscoverer {
+        WordDiscoverer {
+            re_han: jieba.re_han_detail.clone(),
+            max_len: 4,
+            min_count: 5,
+            min_pmi: 3f64,
+            min_entropy: 1f64,
+            max_ngrams: 1_000_000,
+            pruned: 0,
+            total: 0,
+            ngrams: HashMap::new(),
+        }
+    }
+
+    //候选词的最大字数, 默认为 4, 需要在 add_text 之前设置
+    pub fn set_max_len(&mut self, max_len: usize) -> JResult<()> {
+        if max_len < 2 {
+            return Err(Error::InvalidOption(format!(
+                "max_len must be at least 2, got {}",
+                max_len
+            )));
+        }
+        self.max_len = max_len;
+        Ok(())
+    }
+
+    //最少出现次数, 默认为 5
+    pub fn set_min_count(&mut self, min_count: u64) {
+        self.min_count = min_count;
+    }
+
+    //最小凝固度 (自然对数), 默认为 3
+    pub fn set_min_pmi(&mut self, min_pmi: f64) {
+        self.min_pmi = min_pmi;
+    }
+
+    //最小自由度 (自然对数), 默认为 1
+    pub fn set_min_entropy(&mut self, min_entropy: f64) {
+        self.min_entropy = min_entropy;
+    }
+
+    //最多保存的 n-gram 数, 默认为 1000000, 超过时删除出现次数较少的多字 n-gram
+    pub fn set_max_ngrams(&mut self, max_ngrams: usize) {
+        self.max_ngrams = max_ngrams;
+    }
+
+    //已删除出现次数不超过该值的多字 n-gram, 为 0 时没有删除过
+    pub fn pruned_below(&self) -> u64 {
+        self.pruned
+    }
+
+    pub fn add_text(&mut self, text: &str) {
+        for m in self.re_han.find_iter(text) {
+            let chars: Vec<char> = m.as_str().chars().collect();
+            self.total += chars.len() as u64;
+            for i in 0..chars.len() {
+                for n in 1..=self.max_len.min(chars.len() - i) {
+                    let word: String = chars[i..i + n].iter().collect();
+                    let ngram = self.ngrams.entry(word).or_default();
+                    ngram.count += 1;
+                    //单字只用于计算凝固度, 不需要邻字
+                    if n == 1 {
+                        continue;
+                    }
+                    match i.checked_sub(1) {
+                        Some(l) => *ngram.left.entry(chars[l]).or_insert(0) += 1,
+                        None => ngram.left_bound += 1,
+                    }
+                    match chars.get(i + n) {
+                        Some(r) => *ngram.right.entry(*r).or_insert(0) += 1,
+                        None => ngram.right_bound += 1,
+                    }
+                }
+            }
+        }
+        if self.ngrams.len() > self.max_ngrams {
+            self.prune();
+        }
+    }
+
+    // 删除出现次数不超过 pruned 的多字 n-gram, 还是超过 max_ngrams 的一半时逐步提高 pruned.
+    // 单字用于计算凝固度, 不删除. n-gram 的子串出现次数不会更少, 所以保留下来的 n-gram 的子串也都会保留
+    fn prune(&mut self) {
+        loop {
+            let pruned = self.pruned;
+            let mut remaining = 0;
+            self.ngrams.retain(|word, ngram| {
+                if word.chars().nth(1).is_none() {
+                    return true;
+                }
+                let keep = ngram.count > pruned;
+                remaining += keep as usize;
+                keep
+            });
+            if self.ngrams.len() <= self.max_ngrams / 2 || remaining == 0 {
+                break;
+            }
+            self.pruned += 1;
+        }
+    }
+
+    pub fn add_reader<R: BufRead>(&mut self, reader: R) -> JResult<()> {
+        for line in reader.lines() {
+            self.add_text(&line?);
+        }
+        Ok(())
+    }
+
+    fn count(&self, word: &str) -> u64 {
+        self.ngrams.get(word).map(|n| n.count).unwrap_or(0)
+    }
+
+    //按任意位置拆成两部分的最小点互信息
+    fn pmi(&self, word: &str, count: u64) -> f64 {
+        let total = self.total as f64;
+        word.char_indices()
+            .skip(1)
+            .map(|(i, _)| {
+                let a = self.count(&word[..i]) as f64;
+                let b = self.count(&word[i..]) as f64;
+                (count as f64 * total / (a * b)).ln()
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    // 返回得分最高的 top_k 个新词, 得分为 ln(count) * (pmi + min(left_entropy, right_entropy))
+    // 得分相同时按词排序
+    pub fn discover(&self, jieba: &Jieba, top_k: usize) -> Vec<NewWord> {
+        let mut words: Vec<NewWord> = self
+            .ngrams
+            .iter()
+            .filter(|(word, ngram)| ngram.count >= self.min_count && word.chars().nth(1).is_some())
+            .filter(|(word, _)| !matches!(jieba.dict.frequency(word), Some(f) if f > 0f64))
+            .filter_map(|(word, ngram)| {
+                let pmi = self.pmi(word, ngram.count);
+                if pmi < self.min_pmi {
+                    return None;
+                }
+                let left_entropy = entropy(&ngram.left, ngram.left_bound);
+                let right_entropy = entropy(&ngram.right, ngram.right_bound);
+                let free = left_entropy.min(right_entropy);
+                if free < self.min_entropy {
+                    return None;
+                }
+                Some(NewWord {
+                    word: word.clone(),
+                    count: ngram.count,
+                    pmi,
+                    left_entropy,
+                    right_entropy,
+                    score: (ngram.count as f64).ln() * (pmi + free),
+                    freq: 0f64,
+                })
+            })
+            .collect();
+        words.sort_unstable_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.word.cmp(&b.word))
+        });
+        words.truncate(top_k);
+        for w in words.iter_mut() {
+            w.freq = jieba.suggested_freq(&w.word).max(w.count as f64);
+        }
+        words
+    }
+}
+
+//邻字的信息熵, bound 次没有邻字的出现各算作一个不同的邻字
+fn entropy(neighbors: &HashMap<char, u64>, bound: u64) -> f64 {
+    let total = (neighbors.values().sum::<u64>() + bound) as f64;
+    if total == 0f64 {
+        return 0f64;
+    }
+    let h: f64 = neighbors
+        .values()
+        .map(|c| {
+            let p = *c as f64 / total;
+            -p * p.ln()
+        })
+        .sum();
+    h + bound as f64 * (total.ln() / total)
+}
+
+//按用户词典的格式写出, 每行 `word freq`
+pub fn write_user_dict<W: Write>(words: &[NewWord], writer: &mut W) -> std::io::Result<()> {
+    for w in words {
+        writeln!(writer, "{} {}", w.word, w.freq.ceil())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static CORPUS: &str = "
+今天真的蓝瘦香菇了
+他说蓝瘦香菇的意思
+我们都蓝瘦香菇吧
+听完以后很蓝瘦香菇
+蓝瘦香菇成为网络流行语
+大家看到蓝瘦香菇都笑了
+";
+
+    #[test]
+    fn test_entropy() {
+        let mut neighbors = HashMap::new();
+        neighbors.insert('a', 2);
+        neighbors.insert('b', 2);
+        assert!((entropy(&neighbors, 0) - 2f64.ln()).abs() < 1e-9);
+        assert!((entropy(&HashMap::new(), 4) - 4f64.ln()).abs() < 1e-9);
+        assert_eq!(entropy(&HashMap::new(), 0), 0f64);
+    }
+
+    #[test]
+    fn test_discover() {
+        let mut jieba = Jieba::new().unwrap();
+        let mut discoverer = WordDiscoverer::new(&jieba);
+        discoverer.add_reader(CORPUS.as_bytes()).unwrap();
+        discoverer.set_min_count(3);
+        //语料很小, 凝固度不高
+        discoverer.set_min_pmi(1f64);
+        let words = discoverer.discover(&jieba, 3);
+        assert_eq!(words[0].word, "蓝瘦香菇");
+        assert_eq!(words[0].count, 6);
+        assert!(words[0].left_entropy >= 1f64 && words[0].right_entropy >= 1f64);
+        //子串的自由度低
+        assert!(words.iter().all(|w| w.word != "蓝瘦" && w.word != "瘦香菇"));
+
+        let mut data = Vec::new();
+        write_user_dict(&words[..1], &mut data).unwrap();
+        assert!(!jieba
+            .cut("他们蓝瘦香菇了", false, false)
+            .contains(&words[0].word.as_str()));
+        jieba.load_user_dict_from_reader(data.as_slice()).unwrap();
+        let cut = jieba.cut("他们蓝瘦香菇了", false, false);
+        assert!(cut.contains(&words[0].word.as_str()));
+        assert_eq!(cut, vec!["他们", "蓝瘦香菇", "了"]);
+
+        assert!(discoverer.set_max_len(1).is_err());
+    }
+
+    #[test]
+    fn test_han_ranges() {
+        //扩展 A 区的字不在 LEGACY_HAN_RANGES 中, 会切开连续的汉字
+        let jieba = Jieba::builder()
+            .han_ranges(crate::LEGACY_HAN_RANGES)
+            .build()
+            .unwrap();
+        let mut discoverer = WordDiscoverer::new(&jieba);
+        discoverer.add_text("蓝㐀瘦");
+        assert_eq!(discoverer.total, 2);
+        assert_eq!(discoverer.count("蓝㐀"), 0);
+
+        let mut discoverer = WordDiscoverer::new(&Jieba::new().unwrap());
+        discoverer.add_text("蓝㐀瘦");
+        assert_eq!(discoverer.total, 3);
+        assert_eq!(discoverer.count("蓝㐀"), 1);
+    }
+
+    #[test]
+    fn test_prune() {
+        let jieba = Jieba::new().unwrap();
+        let mut discoverer = WordDiscoverer::new(&jieba);
+        for _ in 0..4 {
+            discoverer.add_reader(CORPUS.as_bytes()).unwrap();
+        }
+        let len = discoverer.ngrams.len();
+        let count = discoverer.count("蓝");
+        assert_eq!(discoverer.pruned_below(), 0);
+
+        //超过上限后删除出现次数少的 n-gram, 单字和常见的词的计数不受影响
+        discoverer.set_max_ngrams(len - 1);
+        discoverer.add_text("蓝瘦香菇");
+        assert!(discoverer.pruned_below() > 0);
+        assert!(discoverer.ngrams.len() <= len / 2);
+        assert_eq!(discoverer.count("蓝"), count + 1);
+        assert_eq!(discoverer.count("蓝瘦香菇"), 25);
+
+        discoverer.set_min_count(3);
+        discoverer.set_min_pmi(1f64);
+        assert_eq!(discoverer.discover(&jieba, 1)[0].word, "蓝瘦香菇");
+    }
+}
@@ -1,4 +1,3 @@
-use std::fmt;
 use std::io;
 use std::io::Error as IOError;
 use thiserror::Error;
@@ -10,6 +9,16 @@ pub enum Error {
     UnexpectIO(String, io::Error),
     #[error("Unexpected: {0}")]
     Unexpected(String),
+    #[error("Invalid dictionary entry at line {0}: {1:?}")]
+    InvalidDictEntry(usize, String),
+    #[error("Invalid model entry at line {0}: {1:?}")]
+    InvalidModelEntry(usize, String),
+    #[error("Invalid compiled dictionary: {0}")]
+    InvalidCompiledDict(String),
+    #[error("Invalid option: {0}")]
+    InvalidOption(String),
+    #[error("Invalid frequency {1} for word {0:?}")]
+    InvalidFrequency(String, f64),
 }
 
 impl From<&str> for Error {
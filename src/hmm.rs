@@ -1,17 +1,13 @@
-use crate::hmm_data::{PROB_EMITS, PROB_START, PROB_TRANS};
+use crate::hmm_model::HmmModel;
 use crate::segment::SegmentMatches;
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::cmp::Ordering;
-use std::str::Chars;
 
 lazy_static! {
-    static ref RE_HAN: Regex = Regex::new(r"([\u{4E00}-\u{9FD5}]+)").unwrap();
     static ref RE_SKIP: Regex = Regex::new(r"([a-zA-Z0-9]+(?:.\d+)?%?)").unwrap();
 }
 
-const MIN_FLOAT: f64 = -3.14e100;
-
 #[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
 pub(crate) enum Status {
     B = 0,
@@ -27,10 +23,16 @@ static PREV_STATUS: [[Status; 2]; 4] = [
     [Status::S, Status::E], // S
 ];
 
-pub(crate) fn cut<'a>(sentence: &'a str, words: &mut Vec<&'a str>) {
-    if RE_HAN.is_match(sentence) {
+//re_han 匹配汉字, 包含汉字的文本才使用 HMM
+pub(crate) fn cut<'a>(
+    model: &HmmModel,
+    re_han: &Regex,
+    sentence: &'a str,
+    words: &mut Vec<&'a str>,
+) {
+    if re_han.is_match(sentence) {
         if sentence.chars().count() > 1 {
-            cut_han(sentence, words);
+            cut_han(model, sentence, words);
         } else {
             words.push(sentence);
         }
@@ -46,9 +48,9 @@ pub(crate) fn cut<'a>(sentence: &'a str, words: &mut Vec<&'a str>) {
     }
 }
 
-fn cut_han<'a>(sentence: &'a str, words: &mut Vec<&'a str>) {
+fn cut_han<'a>(model: &HmmModel, sentence: &'a str, words: &mut Vec<&'a str>) {
     let str_len = sentence.len();
-    let pos_list = viterbi(sentence);
+    let pos_list = viterbi(model, sentence);
     let mut curr = sentence.char_indices().map(|x| x.0).peekable();
     let mut begin: usize = 0;
     let mut next: usize = 0;
@@ -65,7 +67,7 @@ fn cut_han<'a>(sentence: &'a str, words: &mut Vec<&'a str>) {
             Status::M => {}
             Status::S => {
                 let byte_end = *curr.peek().unwrap_or(&str_len);
-                words.push(&sentence[begin..byte_end]);
+                words.push(&sentence[byte_start..byte_end]);
                 next = byte_end;
             }
         }
@@ -81,65 +83,52 @@ fn cut_han<'a>(sentence: &'a str, words: &mut Vec<&'a str>) {
 // InitStatus :初始状态概率分布
 // 转移概率矩阵Status(i)只和Status(i-1)相关
 // 发射概率矩阵: P(Observed[i], Status[j]) = P(Status[j]) * P(Observed[i]|Status[j])
-fn viterbi(obs: &str) -> Vec<Status> {
-    let str_len = obs.len();
+fn viterbi(model: &HmmModel, obs: &str) -> Vec<Status> {
     let status = [Status::B, Status::M, Status::E, Status::S];
 
-    let R = status.len();
-    let C = obs.chars().count();
-    let mut V: Vec<f64> = vec![0f64; R * C];
+    let r = status.len();
+    let c = obs.chars().count();
+    let mut v: Vec<f64> = vec![0f64; r * c];
 
-    let mut path: Vec<Status> = vec![Status::B; C];
-    let mut prev: Vec<Option<Status>> = vec![None; R * C];
+    let mut path: Vec<Status> = vec![Status::B; c];
+    let mut prev: Vec<Option<Status>> = vec![None; r * c];
 
-    let mut curr = obs.char_indices().map(|x| x.0).peekable();
-    let x1 = curr.next().unwrap();
-    let x2 = *curr.peek().unwrap();
+    let mut chars = obs.chars();
+    let first = chars.next().unwrap();
     for y in &status {
-        let first_word = &obs[x1..x2];
         let _y = *y as usize;
-        let prob = PROB_START[_y] + PROB_EMITS[_y].get(first_word).cloned().unwrap_or(MIN_FLOAT);
-        V[_y] = prob;
+        v[_y] = model.start[_y] + model.emit_prob(_y, first);
     }
 
-    let mut t = 1;
-    while let Some(byte_start) = curr.next() {
-        let byte_end = *curr.peek().unwrap_or(&str_len);
-        let word = &obs[byte_start..byte_end];
+    for (t, ch) in chars.enumerate().map(|(i, ch)| (i + 1, ch)) {
         for y in &status {
             let _y = *y as usize;
-            let em_prob = PROB_EMITS[_y].get(word).cloned().unwrap_or(MIN_FLOAT);
+            let em_prob = model.emit_prob(_y, ch);
             let (prob, state) = PREV_STATUS[_y]
                 .iter()
                 .map(|y0| {
                     let _y0 = *y0 as usize;
-                    (
-                        V[(t - 1) * R + _y0]
-                            + PROB_TRANS[_y0].get(_y).cloned().unwrap_or(MIN_FLOAT)
-                            + em_prob,
-                        *y0,
-                    )
+                    (v[(t - 1) * r + _y0] + model.trans[_y0][_y] + em_prob, *y0)
                 })
                 .max_by(|x, y| x.partial_cmp(y).unwrap_or(Ordering::Equal))
                 .unwrap();
-            let idx = (t * R) + (*y as usize);
-            V[idx] = prob;
+            let idx = (t * r) + _y;
+            v[idx] = prob;
             prev[idx] = Some(state);
         }
-        t += 1;
     }
     //最后一个字的状态只可能是 E 或者 S，不可能是 M 或者 B, 只需要比较 weight[1(E)][14] 和 weight[3(S)][14] 的大小
     let (_prob, state) = [Status::E, Status::S]
         .iter()
-        .map(|y| (V[(C - 1) * R + (*y as usize)], y))
+        .map(|y| (v[(c - 1) * r + (*y as usize)], y))
         .max_by(|x, y| x.partial_cmp(y).unwrap_or(Ordering::Equal))
         .unwrap();
-    let mut t = C - 1;
+    let mut t = c - 1;
     let mut curr = *state;
     path[t] = *state;
 
     //回溯的路径
-    while let Some(p) = prev[t * R + (curr as usize)] {
+    while let Some(p) = prev[t * r + (curr as usize)] {
         assert!(t > 0);
         path[t - 1] = p;
         curr = p;
@@ -154,14 +143,31 @@ mod tests {
 
     #[test]
     fn test_viterbi() {
-        let path = viterbi("小明硕士毕业于中国科学院计算所");
+        let path = viterbi(&HmmModel::default(), "小明硕士毕业于中国科学院计算所");
         println!("path:{:?}", path);
     }
 
     #[test]
     fn test_cut_han() {
         let mut words: Vec<&str> = Vec::with_capacity(64);
-        cut_han("two", &mut words);
+        cut_han(&HmmModel::default(), "two", &mut words);
         println!("words:{:?}", words);
     }
+
+    #[test]
+    fn test_cut_han_single_after_word() {
+        //S 状态紧跟在 E 之后时, 单字词不能包含前一个词
+        let sentence = "小明硕士毕业于中国科学院计算所";
+        let model = HmmModel::default();
+        let path = viterbi(&model, sentence);
+        assert!(path.windows(2).any(|w| w == [Status::E, Status::S]));
+        let mut words: Vec<&str> = Vec::new();
+        cut_han(&model, sentence, &mut words);
+        //以前 所 会被切成 计算所, 输出的词拼起来比原文多出 计算
+        assert_eq!(
+            words,
+            vec!["小明", "硕士", "毕业于", "中国", "科学院", "计算", "所"]
+        );
+        assert_eq!(words.concat(), sentence);
+    }
 }
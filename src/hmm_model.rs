@@ -0,0 +1,471 @@
+use crate::error::{Error, JResult};
+use crate::hmm_data::{PROB_EMITS, PROB_START, PROB_TRANS};
+use crate::segment::DEFAULT_HAN_RANGES;
+use lazy_static::lazy_static;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::Arc;
+
+const MIN_FLOAT: f64 = -3.14e100;
+
+//状态的顺序与 hmm::Status 相同
+const STATES: [char; 4] = ['B', 'E', 'M', 'S'];
+const B: usize = 0;
+const E: usize = 1;
+const M: usize = 2;
+const S: usize = 3;
+
+//允许的状态转移, 其余转移的概率为 MIN_FLOAT
+const ALLOWED_TRANS: [[usize; 2]; 4] = [
+    [E, M], // B
+    [B, S], // E
+    [E, M], // M
+    [B, S], // S
+];
+
+fn state_index(s: &str) -> Option<usize> {
+    match s {
+        "B" => Some(B),
+        "E" => Some(E),
+        "M" => Some(M),
+        "S" => Some(S),
+        _ => None,
+    }
+}
+
+lazy_static! {
+    //内置的模型, 所有 Jieba 实例默认共享
+    pub(crate) static ref DEFAULT_HMM_MODEL: Arc<HmmModel> = Arc::new(HmmModel::default());
+}
+
+// 识别未登录词使用的 BMES HMM, 与 jieba finalseg 的 prob_start/prob_trans/prob_emit 对应
+// 文本格式每行一条记录, 概率均为对数概率:
+//   start <BMES> <prob>
+//   trans <BMES> <BMES> <prob>
+//   emit <BMES> <char> <prob>
+//   unseen <BMES> <prob>
+// unseen 为没有 emit 记录的字的发射概率, 省略时为 -3.14e100
+#[derive(Debug, Clone, PartialEq)]
+pub struct HmmModel {
+    pub(crate) start: [f64; 4],
+    pub(crate) trans: [[f64; 4]; 4],
+    pub(crate) emit: [HashMap<char, f64>; 4],
+    pub(crate) unseen: [f64; 4],
+}
+
+//使用内置的 hmm_data 概率表
+impl Default for HmmModel {
+    fn default() -> Self {
+        let mut model = HmmModel::empty();
+        model.start = PROB_START;
+        model.trans = PROB_TRANS;
+        for (s, emit) in PROB_EMITS.iter().enumerate() {
+            model.emit[s] = emit
+                .entries()
+                .filter_map(|(k, p)| k.chars().next().map(|c| (c, *p)))
+                .collect();
+        }
+        model
+    }
+}
+
+impl HmmModel {
+    fn empty() -> HmmModel {
+        HmmModel {
+            start: [MIN_FLOAT; 4],
+            trans: [[MIN_FLOAT; 4]; 4],
+            emit: Default::default(),
+            unseen: [MIN_FLOAT; 4],
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> JResult<HmmModel> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|e| Error::UnexpectIO(format!("open {}", path.display()), e))?;
+        HmmModel::from_reader(BufReader::new(file))
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> JResult<HmmModel> {
+        let mut model = HmmModel::empty();
+        let mut has_emit = false;
+        for (i, res_line) in reader.lines().enumerate() {
+            let line = res_line?;
+            let elem = line.split_whitespace().collect::<Vec<&str>>();
+            if elem.is_empty() || elem[0].starts_with('#') {
+                continue;
+            }
+            let invalid = || Error::InvalidModelEntry(i + 1, line.to_string());
+            let prob = elem
+                .last()
+                .and_then(|p| p.parse::<f64>().ok())
+                .ok_or_else(invalid)?;
+            let s = elem
+                .get(1)
+                .and_then(|s| state_index(s))
+                .ok_or_else(invalid)?;
+            match (elem[0], elem.len()) {
+                ("start", 3) => model.start[s] = prob,
+                ("unseen", 3) => model.unseen[s] = prob,
+                ("trans", 4) => {
+                    let s1 = state_index(elem[2]).ok_or_else(invalid)?;
+                    model.trans[s][s1] = prob;
+                }
+                ("emit", 4) => {
+                    let mut cs = elem[2].chars();
+                    let c = match (cs.next(), cs.next()) {
+                        (Some(c), None) => c,
+                        _ => return Err(invalid()),
+                    };
+                    model.emit[s].insert(c, prob);
+                    has_emit = true;
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        if !has_emit {
+            return Err(Error::Unexpected("empty hmm model".to_string()));
+        }
+        Ok(model)
+    }
+
+    //读取 jieba finalseg 的 prob_start.py/prob_trans.py/prob_emit.py 转换成的 JSON,
+    //例如 json.dump({"start": prob_start.P, "trans": prob_trans.P, "emit": prob_emit.P}, f)
+    #[cfg(feature = "json")]
+    pub fn load_json<P: AsRef<Path>>(path: P) -> JResult<HmmModel> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|e| Error::UnexpectIO(format!("open {}", path.display()), e))?;
+        HmmModel::from_json_reader(BufReader::new(file))
+    }
+
+    #[cfg(feature = "json")]
+    pub fn from_json_reader<R: std::io::Read>(reader: R) -> JResult<HmmModel> {
+        use serde_json::{Map, Value};
+
+        let invalid = |msg: String| Error::Unexpected(format!("invalid hmm model json: {}", msg));
+        let root: Value = serde_json::from_reader(reader).map_err(|e| invalid(e.to_string()))?;
+        let object = |v: Option<&Value>, name: &str| -> JResult<Map<String, Value>> {
+            v.and_then(|v| v.as_object())
+                .cloned()
+                .ok_or_else(|| invalid(format!("{} is not an object", name)))
+        };
+        let state =
+            |k: &str| state_index(k).ok_or_else(|| invalid(format!("unknown state {:?}", k)));
+        let prob = |v: &Value, name: &str| {
+            v.as_f64()
+                .ok_or_else(|| invalid(format!("{} is not a number", name)))
+        };
+
+        let mut model = HmmModel::empty();
+        for (k, v) in object(root.get("start"), "start")? {
+            model.start[state(&k)?] = prob(&v, &k)?;
+        }
+        for (k0, v) in object(root.get("trans"), "trans")? {
+            let s0 = state(&k0)?;
+            for (k1, v) in object(Some(&v), &k0)? {
+                model.trans[s0][state(&k1)?] = prob(&v, &k1)?;
+            }
+        }
+        for (k, v) in object(root.get("emit"), "emit")? {
+            let s = state(&k)?;
+            for (w, v) in object(Some(&v), &k)? {
+                let mut cs = w.chars();
+                let c = match (cs.next(), cs.next()) {
+                    (Some(c), None) => c,
+                    _ => return Err(invalid(format!("emit key {:?} is not a char", w))),
+                };
+                model.emit[s].insert(c, prob(&v, &w)?);
+            }
+        }
+        if let Some(unseen) = root.get("unseen") {
+            for (k, v) in object(Some(unseen), "unseen")? {
+                model.unseen[state(&k)?] = prob(&v, &k)?;
+            }
+        }
+        if model.emit.iter().all(|e| e.is_empty()) {
+            return Err(Error::Unexpected("empty hmm model".to_string()));
+        }
+        Ok(model)
+    }
+
+    //字 c 在状态 s 下的发射概率
+    #[inline]
+    pub(crate) fn emit_prob(&self, s: usize, c: char) -> f64 {
+        self.emit[s].get(&c).copied().unwrap_or(self.unseen[s])
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> JResult<()> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .map_err(|e| Error::UnexpectIO(format!("create {}", path.display()), e))?;
+        let mut writer = BufWriter::new(file);
+        self.write_to(&mut writer)
+            .and_then(|_| writer.flush())
+            .map_err(|e| Error::UnexpectIO(format!("write {}", path.display()), e))
+    }
+
+    //按 from_reader 的格式写出, 发射概率按字排序
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        for (s, name) in STATES.iter().enumerate() {
+            writeln!(writer, "start {} {}", name, self.start[s])?;
+        }
+        for (s0, name0) in STATES.iter().enumerate() {
+            for (s1, name1) in STATES.iter().enumerate() {
+                if self.trans[s0][s1] > MIN_FLOAT {
+                    writeln!(writer, "trans {} {} {}", name0, name1, self.trans[s0][s1])?;
+                }
+            }
+        }
+        for (s, name) in STATES.iter().enumerate() {
+            if self.unseen[s] > MIN_FLOAT {
+                writeln!(writer, "unseen {} {}", name, self.unseen[s])?;
+            }
+            let mut emit: Vec<(&char, &f64)> = self.emit[s].iter().collect();
+            emit.sort_unstable_by_key(|(c, _)| **c);
+            for (c, prob) in emit {
+                writeln!(writer, "emit {} {} {}", name, c, prob)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// 从空格分隔的已分词语料训练 HmmModel
+// 每个词按字标注为 B/M/E/S, 连续的汉字词组成一个观察序列, 标点、数字等非汉字词会把序列切开.
+// 概率使用加法平滑, 只在允许的状态转移之间分配
+pub struct HmmTrainer {
+    smoothing: f64,
+    han_ranges: Vec<RangeInclusive<char>>,
+    start: [u64; 4],
+    trans: [[u64; 4]; 4],
+    emit: [HashMap<char, u64>; 4],
+}
+
+impl Default for HmmTrainer {
+    fn default() -> Self {
+        HmmTrainer::new()
+    }
+}
+
+impl HmmTrainer {
+    pub fn new() -> HmmTrainer {
+        HmmTrainer {
+            smoothing: 1f64,
+            han_ranges: DEFAULT_HAN_RANGES.to_vec(),
+            start: [0; 4],
+            trans: [[0; 4]; 4],
+            emit: Default::default(),
+        }
+    }
+
+    //加法平滑的系数, 默认为 1, 为 0 时不平滑
+    pub fn set_smoothing(&mut self, smoothing: f64) {
+        self.smoothing = smoothing.max(0f64);
+    }
+
+    //汉字的范围, 默认为 DEFAULT_HAN_RANGES, 应与使用该模型的 JiebaBuilder::han_ranges 一致
+    pub fn set_han_ranges(&mut self, ranges: &[RangeInclusive<char>]) {
+        self.han_ranges = ranges.to_vec();
+    }
+
+    fn is_han(&self, c: char) -> bool {
+        self.han_ranges.iter().any(|r| r.contains(&c))
+    }
+
+    //加入一行已分词的文本, 词之间用空白分隔
+    pub fn add_line(&mut self, line: &str) {
+        let mut prev: Option<usize> = None;
+        for word in line.split_whitespace() {
+            if !word.chars().all(|c| self.is_han(c)) {
+                prev = None;
+                continue;
+            }
+            let len = word.chars().count();
+            for (i, c) in word.chars().enumerate() {
+                let s = match (i, len) {
+                    (_, 1) => S,
+                    (0, _) => B,
+                    (i, len) if i == len - 1 => E,
+                    _ => M,
+                };
+                match prev {
+                    Some(p) => self.trans[p][s] += 1,
+                    None => self.start[s] += 1,
+                }
+                *self.emit[s].entry(c).or_insert(0) += 1;
+                prev = Some(s);
+            }
+        }
+    }
+
+    pub fn add_reader<R: BufRead>(&mut self, reader: R) -> JResult<()> {
+        for line in reader.lines() {
+            self.add_line(&line?);
+        }
+        Ok(())
+    }
+
+    pub fn model(&self) -> HmmModel {
+        let alpha = self.smoothing;
+        let mut model = HmmModel::empty();
+        //计数为 0 且不平滑时保持 MIN_FLOAT
+        let log_prob = |count: u64, total: f64| {
+            let p = (count as f64 + alpha) / total;
+            if p > 0f64 {
+                p.ln()
+            } else {
+                MIN_FLOAT
+            }
+        };
+        let start_total = (self.start[B] + self.start[S]) as f64 + 2f64 * alpha;
+        for s in [B, S] {
+            model.start[s] = log_prob(self.start[s], start_total);
+        }
+        for (s0, allowed) in ALLOWED_TRANS.iter().enumerate() {
+            let total =
+                allowed.iter().map(|s1| self.trans[s0][*s1]).sum::<u64>() as f64 + 2f64 * alpha;
+            for s1 in allowed {
+                model.trans[s0][*s1] = log_prob(self.trans[s0][*s1], total);
+            }
+        }
+        let vocab: HashSet<char> = self.emit.iter().flat_map(|e| e.keys().copied()).collect();
+        //为没有出现过的字留出一份平滑的概率
+        let v = (vocab.len() + 1) as f64;
+        for s in 0..4 {
+            let total = self.emit[s].values().sum::<u64>() as f64 + alpha * v;
+            if total == 0f64 {
+                continue;
+            }
+            for (c, count) in self.emit[s].iter() {
+                model.emit[s].insert(*c, ((*count as f64 + alpha) / total).ln());
+            }
+            if alpha > 0f64 {
+                model.unseen[s] = (alpha / total).ln();
+            }
+        }
+        model
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static CORPUS: &str = "
+我 来到 北京 清华大学 。
+他 来到 了 网易 杭研 大厦
+小明 硕士 毕业 于 中国 科学院 计算所 , 后 在 日本 京都 大学 深造
+";
+
+    #[test]
+    fn test_train() {
+        let mut trainer = HmmTrainer::new();
+        trainer.add_reader(CORPUS.as_bytes()).unwrap();
+        assert_eq!(trainer.start, [1, 0, 0, 3]);
+        assert_eq!(trainer.emit[S].get(&'我'), Some(&1));
+        assert_eq!(trainer.emit[M].get(&'华'), Some(&1));
+        assert_eq!(trainer.emit[M].get(&'大'), Some(&1));
+        assert_eq!(trainer.emit[E].get(&'学'), Some(&2));
+        assert!(!trainer.emit.iter().any(|e| e.contains_key(&'。')));
+
+        let model = trainer.model();
+        assert_eq!(model.start[E], MIN_FLOAT);
+        assert!((model.start[B].exp() + model.start[S].exp() - 1f64).abs() < 1e-9);
+        for (s0, allowed) in ALLOWED_TRANS.iter().enumerate() {
+            let sum: f64 = allowed.iter().map(|s1| model.trans[s0][*s1].exp()).sum();
+            assert!((sum - 1f64).abs() < 1e-9);
+        }
+        assert_eq!(model.trans[B][S], MIN_FLOAT);
+        for s in 0..4 {
+            let sum: f64 = model.emit[s].values().map(|p| p.exp()).sum::<f64>();
+            assert!(sum < 1f64);
+            assert!(model.emit[s].values().all(|p| *p > model.unseen[s]));
+        }
+    }
+
+    #[test]
+    fn test_han_ranges() {
+        let mut trainer = HmmTrainer::new();
+        trainer.add_line("㐀 我");
+        assert_eq!(trainer.trans[S][S], 1);
+
+        //扩展 A 区的字不在 LEGACY_HAN_RANGES 中, 会切开观察序列
+        let mut trainer = HmmTrainer::new();
+        trainer.set_han_ranges(crate::LEGACY_HAN_RANGES);
+        trainer.add_line("㐀 我");
+        assert_eq!(trainer.trans[S][S], 0);
+        assert!(!trainer.emit[S].contains_key(&'㐀'));
+
+        let mut trainer = HmmTrainer::new();
+        trainer.set_han_ranges(&['ぁ'..='ゖ']);
+        trainer.add_line("あい 我");
+        assert_eq!(trainer.emit[B].get(&'あ'), Some(&1));
+        assert!(!trainer.emit[S].contains_key(&'我'));
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut trainer = HmmTrainer::new();
+        trainer.set_smoothing(0.5);
+        trainer.add_reader(CORPUS.as_bytes()).unwrap();
+        let model = trainer.model();
+        let mut data = Vec::new();
+        model.write_to(&mut data).unwrap();
+        let loaded = HmmModel::from_reader(data.as_slice()).unwrap();
+        assert_eq!(loaded, model);
+
+        assert!(HmmModel::from_reader("start B -0.2\n".as_bytes()).is_err());
+        trainer.set_smoothing(0f64);
+        let model = trainer.model();
+        assert_eq!(model.unseen, [MIN_FLOAT; 4]);
+        assert!(model.trans.iter().flatten().all(|p| !p.is_nan()));
+
+        assert!(matches!(
+            HmmModel::from_reader("emit X 中 -1.0\n".as_bytes()),
+            Err(Error::InvalidModelEntry(1, _))
+        ));
+        assert!(matches!(
+            HmmModel::from_reader("# comment\ntrans B E\n".as_bytes()),
+            Err(Error::InvalidModelEntry(2, _))
+        ));
+    }
+
+    #[test]
+    fn test_default_model() {
+        let model = HmmModel::default();
+        assert_eq!(model.start, PROB_START);
+        assert_eq!(model.trans, PROB_TRANS);
+        for (emit, prob_emit) in model.emit.iter().zip(PROB_EMITS.iter()) {
+            assert_eq!(emit.len(), prob_emit.len());
+        }
+        assert_eq!(model.emit_prob(B, '中'), PROB_EMITS[B]["中"]);
+        assert_eq!(model.emit_prob(B, 'a'), MIN_FLOAT);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_from_json() {
+        let data = r#"{
+            "start": {"B": -0.26, "E": -3.14e+100, "M": -3.14e+100, "S": -1.46},
+            "trans": {"B": {"E": -0.51, "M": -0.91}, "E": {"B": -0.58, "S": -0.80},
+                      "M": {"E": -0.33, "M": -1.26}, "S": {"B": -0.72, "S": -0.66}},
+            "emit": {"B": {"中": -5.2}, "E": {"国": -6.1}, "M": {}, "S": {"的": -3.6}}
+        }"#;
+        let model = HmmModel::from_json_reader(data.as_bytes()).unwrap();
+        assert_eq!(model.start[B], -0.26);
+        assert_eq!(model.trans[S][S], -0.66);
+        assert_eq!(model.trans[B][B], MIN_FLOAT);
+        assert_eq!(model.emit_prob(E, '国'), -6.1);
+        assert_eq!(model.emit_prob(M, '国'), MIN_FLOAT);
+
+        assert!(HmmModel::from_json_reader(r#"{"start": {}}"#.as_bytes()).is_err());
+        let data = r#"{"start": {"X": 0}, "trans": {}, "emit": {}}"#;
+        assert!(HmmModel::from_json_reader(data.as_bytes()).is_err());
+        let data = r#"{"start": {}, "trans": {}, "emit": {"B": {"中国": -1}}}"#;
+        assert!(HmmModel::from_json_reader(data.as_bytes()).is_err());
+    }
+}
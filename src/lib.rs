@@ -1,62 +1,279 @@
+pub mod analyse;
+mod builder;
+mod compiled;
 mod dictionary;
+pub mod discover;
 mod error;
 mod hmm;
+//生成的概率表, 保持原有格式
+#[rustfmt::skip]
 mod hmm_data;
+mod hmm_model;
+mod oov;
+mod parallel;
+mod posseg;
 mod segment;
+pub mod stats;
+mod stream;
+#[cfg(feature = "tantivy")]
+mod tokenizer;
 
-use crate::dictionary::Dictionary;
-use crate::error::JResult;
-use crate::segment::{SegmentMatches, SegmentState, RE_HAN_DEFAULT, RE_SKIP_DEAFULT};
+use crate::compiled::{CompiledDict, DictBytes};
+use crate::dictionary::{default_source_hash, parse_dict, Dictionary};
+use crate::segment::{
+    SegmentMatches, SegmentState, RE_HAN_DEFAULT, RE_HAN_DETAIL_DEFAULT, RE_SKIP_DEAFULT,
+};
+use regex::Regex;
+use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::collections::HashMap;
-use std::str::{self, Chars};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+pub use crate::builder::JiebaBuilder;
+pub use crate::error::{Error, JResult};
+pub use crate::hmm_model::{HmmModel, HmmTrainer};
+pub use crate::oov::{HmmSegmenter, OovSegmenter};
+pub use crate::posseg::{PosModel, Tag};
+pub use crate::segment::{DEFAULT_HAN_RANGES, LEGACY_HAN_RANGES};
+pub use crate::stream::{CutIter, CutReader};
+#[cfg(feature = "tantivy")]
+pub use crate::tokenizer::{JiebaTokenStream, JiebaTokenizer};
 
 const DEFAULT_WORD_LEN: usize = 32;
 
 pub struct Jieba {
     dict: Dictionary,
+    keep_non_words: bool,
+    //词性标注时识别新词的 HMM, 为 None 时使用由内置词典统计出的模型
+    pos_model: Option<PosModel>,
+    //识别未登录词, 默认使用内置模型的 HmmSegmenter
+    oov: Arc<dyn OovSegmenter>,
+    //需要分词的文本, 其余部分作为非词语处理
+    re_han: Regex,
+    //汉字, HMM 只处理这部分文本
+    re_han_detail: Regex,
 }
 
 type Route = (f64, usize);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CutMode {
+    //全模式, 输出所有在词典中的词, 不使用 HMM
+    Full,
+    //精确模式
+    Precise,
+    //搜索引擎模式, 长词中在词典里的 2 字词和 3 字词也会输出
+    Search,
+    //细粒度搜索模式, 长词中所有在词典里的子词都会输出
+    SearchFine,
+}
+
+//cut_with 的选项, 默认使用 HMM 并保留原文中的所有字符
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CutOptions {
+    //使用 HMM 识别未登录词, 全模式下无效
+    pub hmm: bool,
+    //输出空白
+    pub keep_whitespace: bool,
+    //输出标点、emoji 等其他非词语字符
+    pub keep_punctuation: bool,
+    //把 ASCII 大写字母转为小写
+    pub lowercase: bool,
+}
+
+impl Default for CutOptions {
+    fn default() -> Self {
+        CutOptions {
+            hmm: true,
+            keep_whitespace: true,
+            keep_punctuation: true,
+            lowercase: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenizeMode {
+    //精确模式
+    Default,
+    //搜索引擎模式, 长词中的 2 字词和 3 字词也会输出
+    Search,
+}
+
+//tokenize 输出的词, start/end 为字节位置, char_start/char_end 为字符位置, 都是左闭右开
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub word: &'a str,
+    pub start: usize,
+    pub end: usize,
+    pub char_start: usize,
+    pub char_end: usize,
+}
+
 // Based on the front-end implementation of efficient word graph scanning of the dictionary, a directed acyclic graph (DAG) composed of all possible word formations in the sentence is generated
 // Use the maximum range segmentation combination based on dynamic search terms
 // For unregistered words, the HMM model based on the ability of Chinese characters to form words is used, and the Viterbi algorithm is used
 impl Jieba {
     pub fn new() -> JResult<Jieba> {
-        let dict = Dictionary::load()?;
-        Ok(Jieba { dict })
-    }
-
-    //获取有向无环图
-    fn dag(&self, sentence: &str) -> HashMap<usize, Vec<usize>> {
-        let mut dag: HashMap<usize, Vec<usize>> = HashMap::new();
-        let mut frag: &str;
-        let mut n = sentence.len();
-        let mut i: usize = 0;
-        for (k, _) in sentence.char_indices().peekable() {
-            let mut tmplist: Vec<usize> = Vec::new();
-            let mut remain = sentence[k..].char_indices().peekable();
-            loop {
-                if let Some((j, _)) = remain.next() {
-                    if j == 0 {
-                        continue;
-                    }
-                    i = k + j;
-                } else {
-                    i = n;
-                }
-                frag = &sentence[k..i];
-                if let Some(f) = self.dict.frequency(frag) {
-                    if f > 0f64 {
-                        tmplist.push(i);
-                    }
-                }
-                if i == n {
-                    break;
-                }
+        JiebaBuilder::new().build()
+    }
+
+    pub fn builder() -> JiebaBuilder {
+        JiebaBuilder::new()
+    }
+
+    fn with_dict(dict: Dictionary) -> Jieba {
+        Jieba {
+            dict,
+            keep_non_words: true,
+            pos_model: None,
+            oov: Arc::new(HmmSegmenter::default()),
+            re_han: RE_HAN_DEFAULT.clone(),
+            re_han_detail: RE_HAN_DETAIL_DEFAULT.clone(),
+        }
+    }
+
+    //打开 save_compiled_dict 生成的预编译词典, 开启 mmap feature 时直接映射文件, 不需要解析
+    pub fn from_compiled_dict<P: AsRef<Path>>(path: P) -> JResult<Jieba> {
+        let dict = CompiledDict::open(DictBytes::open(path)?)?;
+        Ok(Jieba::with_dict(Dictionary::from_compiled(dict)))
+    }
+
+    //使用 include_bytes! 嵌入程序的预编译词典
+    pub fn from_compiled_bytes(bytes: &'static [u8]) -> JResult<Jieba> {
+        let dict = CompiledDict::open(DictBytes::Static(bytes))?;
+        Ok(Jieba::with_dict(Dictionary::from_compiled(dict)))
+    }
+
+    //把当前词典(包括用户词典和调整过的词频)保存为预编译词典, 先写临时文件再改名, 不会破坏正在使用的旧文件
+    pub fn save_compiled_dict<P: AsRef<Path>>(&self, path: P) -> JResult<()> {
+        compiled::write_atomic(path.as_ref(), &self.dict.compile())
+    }
+
+    // 优先使用 cache_path 的预编译词典, 文件不存在、已损坏或与内置词典版本不一致时
+    // 重新解析文本词典并写入 cache_path, 写入失败不影响返回结果
+    pub fn new_with_cache<P: AsRef<Path>>(cache_path: P) -> JResult<Jieba> {
+        let cache_path = cache_path.as_ref();
+        let cached = DictBytes::open(cache_path)
+            .and_then(CompiledDict::open)
+            .ok()
+            .filter(|dict| dict.source_hash == default_source_hash());
+        if let Some(dict) = cached {
+            return Ok(Jieba::with_dict(Dictionary::from_compiled(dict)));
+        }
+        let jieba = Jieba::new()?;
+        let _ = jieba.save_compiled_dict(cache_path);
+        Ok(jieba)
+    }
+
+    //替换识别未登录词使用的 HMM
+    pub fn set_hmm_model(&mut self, model: HmmModel) {
+        self.oov = Arc::new(HmmSegmenter::with_regex(
+            Arc::new(model),
+            self.re_han_detail.clone(),
+        ));
+    }
+
+    //使用自定义的未登录词识别, 替换默认的 HMM
+    pub fn set_oov_segmenter<S: OovSegmenter + 'static>(&mut self, segmenter: S) {
+        self.oov = Arc::new(segmenter);
+    }
+
+    //是否输出标点、空白、emoji 等非词语部分, 默认输出以便还原原文
+    pub fn set_keep_non_words(&mut self, keep: bool) {
+        self.keep_non_words = keep;
+    }
+
+    //加载用户词典, 每行格式为 `word [freq] [tag]`, 省略词频时自动计算一个能让该词被切分出来的词频
+    pub fn load_user_dict<P: AsRef<Path>>(&mut self, path: P) -> JResult<()> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|e| Error::UnexpectIO(format!("open {}", path.display()), e))?;
+        self.load_user_dict_from_reader(BufReader::new(file))
+    }
+
+    pub fn load_user_dict_from_reader<R: BufRead>(&mut self, reader: R) -> JResult<()> {
+        for entry in parse_dict(reader)? {
+            let freq = match entry.freq {
+                Some(f) => f,
+                None => self.suggested_freq(&entry.word),
+            };
+            self.dict.add_word(&entry.word, freq, entry.tag.as_deref());
+        }
+        Ok(())
+    }
+
+    //词典中该词的词性, 不在词典中的词返回 None
+    pub fn tag(&self, word: &str) -> Option<&str> {
+        self.dict.tag(word)
+    }
+
+    //向词典中加入一个词, 省略词频时使用 suggest_freq 计算出的词频, 返回实际使用的词频
+    //省略词性时保留词典中原有的词性, 新词的词性为 x
+    //词频为负数、NaN 或无穷大时返回错误
+    pub fn add_word(&mut self, word: &str, freq: Option<f64>, tag: Option<&str>) -> JResult<f64> {
+        let freq = match freq {
+            Some(f) if !f.is_finite() || f < 0f64 => {
+                return Err(Error::InvalidFrequency(word.to_string(), f))
             }
-            dag.insert(k, tmplist);
+            Some(f) => f,
+            None => self.suggested_freq(word),
+        };
+        self.dict.add_word(word, freq, tag);
+        Ok(freq)
+    }
+
+    //从词典中删除一个词, 之后该词也不会再由 HMM 识别出来
+    pub fn del_word(&mut self, word: &str) {
+        self.dict.del_word(word);
+    }
+
+    //调节词频, 使 segment 能(或不能)被切分出来
+    //segment 只有一个元素时计算让该词整体被切出的词频, 多个元素时计算让它被切成这几段的词频
+    //tune 为 true 时把计算出的词频写回词典
+    pub fn suggest_freq(&mut self, segment: &[&str], tune: bool) -> f64 {
+        let word = segment.concat();
+        let freq = if segment.len() == 1 {
+            self.suggested_freq(&word)
+        } else {
+            let total = self.dict.total;
+            let freq = segment
+                .iter()
+                .map(|seg| self.dict.frequency(seg).unwrap_or(1f64) / total)
+                .product::<f64>();
+            (freq * total)
+                .floor()
+                .min(self.dict.frequency(&word).unwrap_or(0f64))
+        };
+        if tune {
+            let tag = self.dict.tag(&word).map(|t| t.to_string());
+            self.dict.add_word(&word, freq, tag.as_deref());
+        }
+        freq
+    }
+
+    // 与 jieba 的 suggest_freq 相同: 按不使用 HMM 的切分结果估算词频, 保证该词能被切分出来
+    fn suggested_freq(&self, word: &str) -> f64 {
+        let total = self.dict.total;
+        let freq = self
+            .cut(word, false, false)
+            .iter()
+            .map(|seg| self.dict.frequency(seg).unwrap_or(1f64) / total)
+            .product::<f64>();
+        ((freq * total).floor() + 1f64).max(self.dict.frequency(word).unwrap_or(1f64))
+    }
+
+    //获取有向无环图, dag[k] 为从字节位置 k 开始的所有词的结束位置和词频
+    fn dag(&self, sentence: &str) -> Vec<Vec<(usize, f64)>> {
+        let mut dag: Vec<Vec<(usize, f64)>> = vec![Vec::new(); sentence.len()];
+        for (k, _) in sentence.char_indices() {
+            dag[k] = self
+                .dict
+                .prefixes(&sentence[k..])
+                .map(|(end, freq)| (k + end, freq))
+                .collect();
         }
         dag
     }
@@ -69,17 +286,10 @@ impl Jieba {
         let byte_index = sentence.char_indices().map(|x| x.0).rev();
         let mut prev_byte_start = str_len;
         for byte_start in byte_index {
-            let l = dag.get(&byte_start).unwrap();
-            let pair = l
+            let pair = dag[byte_start]
                 .iter()
-                .map(|byte_end| {
-                    let mut f: f64 = 0.0;
-                    if let Some(freq) = self.dict.frequency(&sentence[byte_start..*byte_end]) {
-                        f = freq.ln() - self.dict.log_total + rs[*byte_end].0;
-                    } else {
-                        f = 1f64.ln() - self.dict.log_total + rs[*byte_end].0;
-                    }
-                    (f, *byte_end)
+                .map(|(byte_end, freq)| {
+                    (freq.ln() - self.dict.log_total + rs[*byte_end].0, *byte_end)
                 })
                 .max_by(|r1, r2| r1.partial_cmp(r2).unwrap_or(Ordering::Equal));
 
@@ -107,21 +317,7 @@ impl Jieba {
                 }
             } else {
                 if let Some(l) = left {
-                    let word = &sentence[l..x];
-                    if word.chars().count() == 1 {
-                        words.push(word);
-                    } else {
-                        let f = self.dict.frequency(word);
-                        if f.is_none() || f == Some(0.0) {
-                            hmm::cut(word, words);
-                        } else {
-                            let mut word_index = word.char_indices().map(|x| x.0).peekable();
-                            while let Some(byte_start) = word_index.next() {
-                                let byte_end = *word_index.peek().unwrap_or(&word.len());
-                                words.push(&word[byte_start..byte_end]);
-                            }
-                        }
-                    }
+                    self.cut_single_chars(&sentence[l..x], words);
                     left = None;
                 }
                 words.push(frag);
@@ -129,19 +325,30 @@ impl Jieba {
             x = y;
         }
         if let Some(l) = left {
-            let word = &sentence[l..];
-            if word.chars().count() == 1 {
-                words.push(word);
-            } else {
-                let f = self.dict.frequency(word);
-                if f.is_none() || f == Some(0.0) {
-                    hmm::cut(word, words);
+            self.cut_single_chars(&sentence[l..], words);
+        }
+    }
+
+    //连续的单字, 不在词典中时交给 HMM 识别新词
+    fn cut_single_chars<'a>(&self, word: &'a str, words: &mut Vec<&'a str>) {
+        if word.chars().count() == 1 {
+            words.push(word);
+            return;
+        }
+        let f = self.dict.frequency(word);
+        if f.is_some() && f != Some(0.0) {
+            push_chars(word, words);
+            return;
+        }
+        let start = words.len();
+        self.oov.cut(word, words);
+        //被删除的词不能再由 HMM 组合出来
+        if words[start..].iter().any(|w| self.dict.is_force_split(w)) {
+            for w in words.split_off(start) {
+                if self.dict.is_force_split(w) {
+                    push_chars(w, words);
                 } else {
-                    let mut word_index = word.char_indices().map(|x| x.0).peekable();
-                    while let Some(byte_start) = word_index.next() {
-                        let byte_end = *word_index.peek().unwrap_or(&word.len());
-                        words.push(&word[byte_start..byte_end]);
-                    }
+                    words.push(w);
                 }
             }
         }
@@ -162,7 +369,7 @@ impl Jieba {
                 continue;
             }
             if let Some(l) = left {
-                words.push(&sentence[l..y]);
+                words.push(&sentence[l..x]);
                 left = None;
             }
             words.push(frag);
@@ -176,57 +383,219 @@ impl Jieba {
     fn cut_all<'a>(&self, sentence: &'a str, words: &mut Vec<&'a str>) {
         let dag = self.dag(sentence);
         //let start: i32 = -1;
-        let byte_index: Vec<usize> = sentence.char_indices().map(|x| x.0).collect();
-        for (i, byte_start) in byte_index.into_iter().enumerate() {
-            let l = dag.get(&byte_start).unwrap();
-            for j in l {
-                words.push(&sentence[byte_start..*j]);
+        for (byte_start, _) in sentence.char_indices() {
+            for (byte_end, _) in dag[byte_start].iter() {
+                words.push(&sentence[byte_start..*byte_end]);
             }
         }
     }
 
+    //cut_all 为 true 时使用全模式并忽略 hmm, 等价于 cut_with(text, CutMode::Full, ..)
     pub fn cut<'a>(&self, text: &'a str, cut_all: bool, hmm: bool) -> Vec<&'a str> {
+        let mode = if cut_all {
+            CutMode::Full
+        } else {
+            CutMode::Precise
+        };
+        self.cut_words(text, mode, self.cut_options(hmm))
+    }
+
+    pub fn cut_for_search<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        self.cut_words(text, CutMode::Search, self.cut_options(true))
+    }
+
+    //按 set_keep_non_words 的设置生成的选项
+    fn cut_options(&self, hmm: bool) -> CutOptions {
+        CutOptions {
+            hmm,
+            keep_whitespace: self.keep_non_words,
+            keep_punctuation: self.keep_non_words,
+            lowercase: false,
+        }
+    }
+
+    //按指定的模式和选项分词, 只有 lowercase 时含大写字母的词才会复制
+    pub fn cut_with<'a>(
+        &self,
+        text: &'a str,
+        mode: CutMode,
+        opts: CutOptions,
+    ) -> Vec<Cow<'a, str>> {
+        self.cut_words(text, mode, opts)
+            .into_iter()
+            .map(|word| apply_lowercase(word, opts))
+            .collect()
+    }
+
+    pub(crate) fn cut_words<'a>(
+        &self,
+        text: &'a str,
+        mode: CutMode,
+        opts: CutOptions,
+    ) -> Vec<&'a str> {
         let mut words: Vec<&str> = Vec::with_capacity(DEFAULT_WORD_LEN);
-        let seg_split = SegmentMatches::new(&RE_HAN_DEFAULT, text);
-        for m in seg_split {
-            match m {
-                SegmentState::Matched(m) => {
-                    if cut_all {
-                        self.cut_all(m.as_str(), &mut words)
-                    } else if hmm {
-                        self.cut_dag_with_hmm(m.as_str(), &mut words)
+        for block in SegmentMatches::new(&self.re_han, text) {
+            self.cut_block(block, mode, opts, &mut words);
+        }
+        words
+    }
+
+    //切分 re_han 分出的一段文本, 结果追加到 words
+    pub(crate) fn cut_block<'a>(
+        &self,
+        block: SegmentState<'a>,
+        mode: CutMode,
+        opts: CutOptions,
+        words: &mut Vec<&'a str>,
+    ) {
+        match block {
+            SegmentState::Matched(m) => match mode {
+                CutMode::Full => self.cut_all(m.as_str(), words),
+                CutMode::Precise if opts.hmm => self.cut_dag_with_hmm(m.as_str(), words),
+                CutMode::Precise => self.cut_dag_no_hmm(m.as_str(), words),
+                CutMode::Search | CutMode::SearchFine => {
+                    let mut base = Vec::with_capacity(DEFAULT_WORD_LEN);
+                    if opts.hmm {
+                        self.cut_dag_with_hmm(m.as_str(), &mut base);
                     } else {
-                        self.cut_dag_no_hmm(m.as_str(), &mut words)
+                        self.cut_dag_no_hmm(m.as_str(), &mut base);
+                    }
+                    for word in base {
+                        if mode == CutMode::Search {
+                            self.cut_grams(word, words);
+                        } else {
+                            self.cut_subwords(word, words);
+                        }
+                        words.push(word);
                     }
                 }
-                SegmentState::Unmatched(s) => {}
-            }
+            },
+            SegmentState::Unmatched(s) => cut_non_words(s, opts, words),
         }
-        words
     }
 
-    pub fn cut_for_search<'a>(&self, text: &'a str) -> Vec<&'a str> {
-        let words = self.cut(text, false, true);
-        let mut new_words = Vec::with_capacity(words.len());
-        for word in words.iter() {
-            let char_len = word.chars().count();
-            let char_index: Vec<usize> = word.char_indices().map(|x| x.0).collect();
-            for v in 2..=3 {
-                if char_len <= v {
-                    continue;
+    // 逐段切分并逐个返回词, 只保存当前一段的结果, 适合很长的文本
+    // 与 cut_with 相同, 只有 lowercase 时含大写字母的词才会复制
+    pub fn cut_iter<'a>(&'a self, text: &'a str, mode: CutMode, opts: CutOptions) -> CutIter<'a> {
+        CutIter::new(self, text, mode, opts)
+    }
+
+    // 从 reader 中读入文本并逐个返回词, 内存占用与文本长度无关, 可以切分超过内存大小的文件
+    // 文本必须是 UTF-8 编码, 否则返回错误
+    pub fn cut_reader<R: BufRead>(
+        &self,
+        reader: R,
+        mode: CutMode,
+        opts: CutOptions,
+    ) -> CutReader<'_, R> {
+        CutReader::new(self, reader, mode, opts)
+    }
+
+    //细粒度搜索模式: 找出长词中所有在词典里的子词, 不包括单字
+    fn cut_subwords<'a>(&self, word: &'a str, words: &mut Vec<&'a str>) {
+        for (start, c) in word.char_indices() {
+            for (end, _) in self.dict.prefixes(&word[start..]) {
+                let end = start + end;
+                if end - start > c.len_utf8() && end - start < word.len() {
+                    words.push(&word[start..end]);
                 }
-                for i in 0..char_len - v {
-                    let garm = &word[char_index[i]..char_index[i + v]];
-                    if let Some(f) = self.dict.frequency(garm) {
-                        if f > 0.0 {
-                            new_words.push(garm);
-                        }
+            }
+        }
+    }
+
+    //含有汉字、字母或数字的词, 标点、空白以及单独的 . - _ % & 等符号不算
+    pub(crate) fn is_word(&self, word: &str) -> bool {
+        word.chars().any(char::is_alphanumeric) || self.re_han_detail.is_match(word)
+    }
+
+    //搜索引擎模式: 找出长词中所有在词典里的 2 字词和 3 字词
+    pub(crate) fn cut_grams<'a>(&self, word: &'a str, words: &mut Vec<&'a str>) {
+        let mut char_index: Vec<usize> = word.char_indices().map(|x| x.0).collect();
+        let char_len = char_index.len();
+        char_index.push(word.len());
+        for v in 2..=3 {
+            if char_len <= v {
+                continue;
+            }
+            for i in 0..=char_len - v {
+                let garm = &word[char_index[i]..char_index[i + v]];
+                if let Some(f) = self.dict.frequency(garm) {
+                    if f > 0.0 {
+                        words.push(garm);
                     }
                 }
             }
-            new_words.push(word);
         }
-        new_words
+    }
+
+    //切分并给出每个词在原文中的字节位置和字符位置
+    pub fn tokenize<'a>(&self, text: &'a str, mode: TokenizeMode, hmm: bool) -> Vec<Token<'a>> {
+        let words = self.cut(text, false, hmm);
+        let mut tokens = Vec::with_capacity(words.len());
+        let mut grams: Vec<&str> = Vec::new();
+        let (mut byte_pos, mut char_pos) = (0usize, 0usize);
+        for word in words {
+            //词按顺序出现在原文中, 中间只可能跳过非词语部分, 从上一个词的末尾向后找
+            let start = byte_pos
+                + text[byte_pos..]
+                    .find(word)
+                    .expect("cut returns words of text in order");
+            char_pos += text[byte_pos..start].chars().count();
+            let char_len = word.chars().count();
+            if mode == TokenizeMode::Search {
+                grams.clear();
+                self.cut_grams(word, &mut grams);
+                for gram in grams.iter() {
+                    let offset = gram.as_ptr() as usize - word.as_ptr() as usize;
+                    let char_offset = char_pos + word[..offset].chars().count();
+                    tokens.push(Token {
+                        word: gram,
+                        start: start + offset,
+                        end: start + offset + gram.len(),
+                        char_start: char_offset,
+                        char_end: char_offset + gram.chars().count(),
+                    });
+                }
+            }
+            tokens.push(Token {
+                word,
+                start,
+                end: start + word.len(),
+                char_start: char_pos,
+                char_end: char_pos + char_len,
+            });
+            byte_pos = start + word.len();
+            char_pos += char_len;
+        }
+        tokens
+    }
+}
+
+//空白字符单独成词, 其余字符逐个切开, 按 opts 决定是否输出
+fn cut_non_words<'a>(s: &'a str, opts: CutOptions, words: &mut Vec<&'a str>) {
+    for state in SegmentMatches::new(&RE_SKIP_DEAFULT, s) {
+        match state {
+            SegmentState::Matched(m) if opts.keep_whitespace => words.push(m.as_str()),
+            SegmentState::Unmatched(x) if opts.keep_punctuation => push_chars(x, words),
+            _ => {}
+        }
+    }
+}
+
+//opts.lowercase 时把含大写字母的词转为小写, 其余的词不复制
+pub(crate) fn apply_lowercase(word: &str, opts: CutOptions) -> Cow<'_, str> {
+    if opts.lowercase && word.bytes().any(|b| b.is_ascii_uppercase()) {
+        Cow::Owned(word.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(word)
+    }
+}
+
+fn push_chars<'a>(word: &'a str, words: &mut Vec<&'a str>) {
+    let mut word_index = word.char_indices().map(|x| x.0).peekable();
+    while let Some(byte_start) = word_index.next() {
+        let byte_end = *word_index.peek().unwrap_or(&word.len());
+        words.push(&word[byte_start..byte_end]);
     }
 }
 
@@ -274,4 +643,341 @@ mod tests {
         let words = jieba.cut_for_search("小明硕士毕业于中国科学院计算所");
         print!("rs:{:?}", words);
     }
+
+    #[test]
+    fn test_cut_for_search_trailing_gram() {
+        let jieba = Jieba::new().unwrap();
+        //以前不输出长词末尾的 2 字词和 3 字词, 得到 ["中国", "科学", "中国科学院"]
+        //与 jieba 一样, 现在末尾的 学院 和 科学院 也会输出
+        assert_eq!(
+            jieba.cut_for_search("中国科学院"),
+            vec!["中国", "科学", "学院", "科学院", "中国科学院"]
+        );
+        assert_eq!(
+            jieba.cut_for_search("清华大学"),
+            vec!["清华", "华大", "大学", "清华大学"]
+        );
+    }
+
+    #[test]
+    fn test_cut_non_words() {
+        let mut jieba = Jieba::new().unwrap();
+        let text = "👪 PS: 我觉得开源有一个好处，\r\n就是能够敦促自己";
+        let words = jieba.cut(text, false, true);
+        assert_eq!(words.concat(), text);
+        assert_eq!(&words[..5], &["👪", " ", "PS", ":", " "]);
+        assert!(words.contains(&"，"));
+        assert!(words.contains(&"\r\n"));
+        assert!(jieba.cut(text, true, false).contains(&"，"));
+
+        jieba.set_keep_non_words(false);
+        let words = jieba.cut(text, false, true);
+        assert_eq!(&words[..2], &["PS", "我"]);
+        assert!(!words.contains(&"，"));
+    }
+
+    #[test]
+    fn test_cut_concat() {
+        //随机拼接汉字、字母数字、标点、空白和 emoji, 不论是否使用 HMM, 词拼起来都等于原文
+        let jieba = Jieba::new().unwrap();
+        let pieces = [
+            "中国", "北京", "杭研", "的", "abc", "iPhone", "12", "3.5%", "a-b", "，", "。", " ",
+            "\r\n", "\t", "👪", "_", "#",
+        ];
+        let mut seed = 7u64;
+        let mut rand = |n: usize| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        for _ in 0..200 {
+            let text: String = (0..1 + rand(12))
+                .map(|_| pieces[rand(pieces.len())])
+                .collect();
+            for hmm in [false, true] {
+                assert_eq!(jieba.cut(&text, false, hmm).concat(), text, "hmm={}", hmm);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let jieba = Jieba::new().unwrap();
+        //不使用 HMM 时字母数字串后紧跟汉字
+        for text in ["abc中国", "iPhone12手机 abc", "中国abc中国"] {
+            for hmm in [false, true] {
+                let tokens = jieba.tokenize(text, TokenizeMode::Search, hmm);
+                let chars: Vec<char> = text.chars().collect();
+                for t in tokens.iter() {
+                    assert_eq!(&text[t.start..t.end], t.word);
+                    let word: String = chars[t.char_start..t.char_end].iter().collect();
+                    assert_eq!(word, t.word);
+                }
+            }
+        }
+
+        let text = "永和服装饰品有限公司";
+        let tokens = jieba.tokenize(text, TokenizeMode::Default, false);
+        assert_eq!(
+            tokens[1],
+            Token {
+                word: "服装",
+                start: 6,
+                end: 12,
+                char_start: 2,
+                char_end: 4,
+            }
+        );
+        for t in tokens.iter() {
+            assert_eq!(&text[t.start..t.end], t.word);
+        }
+
+        let text = "PS: 中国科学院计算所";
+        let tokens = jieba.tokenize(text, TokenizeMode::Search, true);
+        let chars: Vec<char> = text.chars().collect();
+        for t in tokens.iter() {
+            assert_eq!(&text[t.start..t.end], t.word);
+            let word: String = chars[t.char_start..t.char_end].iter().collect();
+            assert_eq!(word, t.word);
+        }
+        let words: Vec<&str> = tokens.iter().map(|t| t.word).collect();
+        assert_eq!(
+            words,
+            vec![
+                "PS",
+                ":",
+                " ",
+                "中国",
+                "科学",
+                "学院",
+                "科学院",
+                "中国科学院",
+                "计算",
+                "计算所"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cut_no_hmm_ascii() {
+        let jieba = Jieba::new().unwrap();
+        assert_eq!(jieba.cut("abc中国", false, false), vec!["abc", "中国"]);
+        assert_eq!(
+            jieba.cut("中国abc12北京", false, false),
+            vec!["中国", "abc12", "北京"]
+        );
+        assert_eq!(jieba.cut("中国abc", false, false), vec!["中国", "abc"]);
+    }
+
+    #[test]
+    fn test_load_user_dict() {
+        let mut jieba = Jieba::new().unwrap();
+        let words = jieba.cut("他来到了网易杭研大厦", false, false);
+        assert_eq!(words, vec!["他", "来到", "了", "网易", "杭", "研", "大厦"]);
+        jieba
+            .load_user_dict_from_reader("杭研 20 nt\n".as_bytes())
+            .unwrap();
+        let words = jieba.cut("他来到了网易杭研大厦", false, false);
+        assert_eq!(words, vec!["他", "来到", "了", "网易", "杭研", "大厦"]);
+        assert!(jieba.load_user_dict("no/such/dict.txt").is_err());
+    }
+
+    #[test]
+    fn test_add_del_word() {
+        let mut jieba = Jieba::new().unwrap();
+        let freq = jieba.add_word("杭研大厦", None, Some("nt")).unwrap();
+        assert!(freq > 0f64);
+        for f in [-1f64, f64::NAN, f64::INFINITY] {
+            let err = jieba.add_word("杭研", Some(f), None);
+            assert!(matches!(err, Err(Error::InvalidFrequency(_, _))));
+        }
+        assert_eq!(jieba.tag("杭研"), None);
+        assert_eq!(jieba.tag("杭研大厦"), Some("nt"));
+        let words = jieba.cut("他来到了网易杭研大厦", false, true);
+        assert_eq!(words, vec!["他", "来到", "了", "网易", "杭研大厦"]);
+
+        jieba.del_word("杭研大厦");
+        assert_eq!(jieba.tag("杭研大厦"), None);
+        let words = jieba.cut("他来到了网易杭研大厦", false, true);
+        assert_eq!(words, vec!["他", "来到", "了", "网易", "杭研", "大厦"]);
+
+        jieba.del_word("杭研");
+        let words = jieba.cut("他来到了网易杭研大厦", false, true);
+        assert_eq!(words, vec!["他", "来到", "了", "网易", "杭", "研", "大厦"]);
+    }
+
+    #[test]
+    fn test_suggest_freq() {
+        let mut jieba = Jieba::new().unwrap();
+        let words = jieba.cut("如果放到post中将出错。", false, false);
+        assert!(words.contains(&"中将"));
+        jieba.suggest_freq(&["中", "将"], true);
+        let words = jieba.cut("如果放到post中将出错。", false, false);
+        assert!(!words.contains(&"中将"));
+
+        let words = jieba.cut("「台中」正确应该不会被切开", false, false);
+        assert!(!words.contains(&"台中"));
+        jieba.suggest_freq(&["台中"], true);
+        let words = jieba.cut("「台中」正确应该不会被切开", false, false);
+        assert!(words.contains(&"台中"));
+    }
+
+    #[test]
+    fn test_load_user_dict_without_freq() {
+        let mut jieba = Jieba::new().unwrap();
+        jieba
+            .load_user_dict_from_reader("杭研 nt\n云计算\n".as_bytes())
+            .unwrap();
+        assert_eq!(jieba.tag("杭研"), Some("nt"));
+        assert_eq!(jieba.tag("云计算"), Some("x"));
+        let words = jieba.cut("他来到了网易杭研大厦", false, false);
+        assert_eq!(words, vec!["他", "来到", "了", "网易", "杭研", "大厦"]);
+    }
+
+    #[test]
+    fn test_compiled_dict() {
+        let path = std::env::temp_dir().join(format!("jiebars-{}.dict", std::process::id()));
+        let mut jieba = Jieba::new().unwrap();
+        jieba.add_word("杭研大厦", None, Some("nt")).unwrap();
+        jieba.save_compiled_dict(&path).unwrap();
+        let compiled = Jieba::from_compiled_dict(&path).unwrap();
+        let text = "我来到北京清华大学, 他来到了网易杭研大厦";
+        assert_eq!(
+            compiled.cut(text, false, true),
+            jieba.cut(text, false, true)
+        );
+        assert_eq!(
+            compiled.cut(text, true, false),
+            jieba.cut(text, true, false)
+        );
+        assert_eq!(compiled.tag("杭研大厦"), Some("nt"));
+
+        let data = std::fs::read(&path).unwrap();
+        let compiled = Jieba::from_compiled_bytes(Box::leak(data.into_boxed_slice())).unwrap();
+        assert_eq!(compiled.cut_for_search(text), jieba.cut_for_search(text));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_new_with_cache() {
+        let path = std::env::temp_dir().join(format!("jiebars-cache-{}.dict", std::process::id()));
+        std::fs::write(&path, b"not a dictionary").unwrap();
+        assert!(Jieba::from_compiled_dict(&path).is_err());
+        // 缓存已损坏, 重新解析文本词典并覆盖缓存
+        let jieba = Jieba::new_with_cache(&path).unwrap();
+        assert!(Jieba::from_compiled_dict(&path).is_ok());
+        let cached = Jieba::new_with_cache(&path).unwrap();
+        let text = "小明硕士毕业于中国科学院计算所";
+        assert_eq!(cached.cut(text, false, true), jieba.cut(text, false, true));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_cut_with() {
+        let jieba = Jieba::new().unwrap();
+        let text = "小明硕士毕业于中国科学院计算所, 后在 Apple 深造";
+        let opts = CutOptions::default();
+        assert_eq!(
+            jieba.cut_with(text, CutMode::Precise, opts),
+            jieba.cut(text, false, true)
+        );
+        assert_eq!(
+            jieba.cut_with(text, CutMode::Full, opts),
+            jieba.cut(text, true, false)
+        );
+        assert_eq!(
+            jieba.cut_with(text, CutMode::Search, opts),
+            jieba.cut_for_search(text)
+        );
+
+        let opts = CutOptions {
+            keep_whitespace: false,
+            keep_punctuation: false,
+            lowercase: true,
+            ..Default::default()
+        };
+        let words = jieba.cut_with(text, CutMode::Precise, opts);
+        assert_eq!(
+            words,
+            vec![
+                "小明",
+                "硕士",
+                "毕业",
+                "于",
+                "中国科学院",
+                "计算所",
+                "后",
+                "在",
+                "apple",
+                "深造"
+            ]
+        );
+        assert!(matches!(words[0], Cow::Borrowed(_)));
+
+        let opts = CutOptions {
+            keep_whitespace: true,
+            keep_punctuation: false,
+            ..Default::default()
+        };
+        let words = jieba.cut_with("你好, 世界", CutMode::Precise, opts);
+        assert_eq!(words, vec!["你好", " ", "世界"]);
+
+        let words = jieba.cut_with("中国科学院", CutMode::SearchFine, CutOptions::default());
+        assert_eq!(words, vec!["中国", "科学", "科学院", "学院", "中国科学院"]);
+    }
+
+    #[test]
+    fn test_hmm_model() {
+        let text = "他来到了网易杭研大厦";
+        let jieba = Jieba::new().unwrap();
+        assert_eq!(
+            jieba.cut(text, false, true),
+            vec!["他", "来到", "了", "网易", "杭研", "大厦"]
+        );
+
+        //只见过单字词的模型不会把 杭研 组合成词
+        let mut trainer = HmmTrainer::new();
+        trainer.add_line("杭 研 杭 研 大 厦 杭 研");
+        let mut custom = Jieba::builder().hmm_model(trainer.model()).build().unwrap();
+        assert_eq!(
+            custom.cut(text, false, true),
+            vec!["他", "来到", "了", "网易", "杭", "研", "大厦"]
+        );
+        assert_eq!(jieba.cut(text, false, true)[4], "杭研");
+
+        custom.set_hmm_model(HmmModel::default());
+        assert_eq!(custom.cut(text, false, true), jieba.cut(text, false, true));
+    }
+
+    #[test]
+    fn test_oov_segmenter() {
+        //未登录词都按单字切分
+        struct SingleChars;
+
+        impl OovSegmenter for SingleChars {
+            fn cut<'a>(&self, fragment: &'a str, words: &mut Vec<&'a str>) {
+                let mut start = 0;
+                for (i, _) in fragment.char_indices().skip(1) {
+                    words.push(&fragment[start..i]);
+                    start = i;
+                }
+                words.push(&fragment[start..]);
+            }
+        }
+
+        let text = "他来到了网易杭研大厦";
+        let jieba = Jieba::builder().oov_segmenter(SingleChars).build().unwrap();
+        assert_eq!(
+            jieba.cut(text, false, true),
+            vec!["他", "来到", "了", "网易", "杭", "研", "大厦"]
+        );
+
+        let mut jieba = Jieba::new().unwrap();
+        assert_eq!(jieba.cut(text, false, true)[4], "杭研");
+        jieba.set_oov_segmenter(SingleChars);
+        assert_eq!(jieba.cut(text, false, true)[4], "杭");
+        jieba.set_oov_segmenter(HmmSegmenter::default());
+        assert_eq!(jieba.cut(text, false, true)[4], "杭研");
+    }
 }
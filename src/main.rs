@@ -0,0 +1,263 @@
+use jiebars::{CutMode, CutOptions, Jieba, JiebaBuilder};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process;
+
+const USAGE: &str = "usage: jiebars [options] [FILE]...
+
+对 FILE 逐行分词, 没有 FILE 或 FILE 为 - 时读取标准输入
+
+options:
+  -d, --delimiter DELIM      词之间的分隔符, 默认为 \" / \"
+  -p, --pos                  输出词性
+      --pos-delimiter DELIM  词和词性之间的分隔符, 默认为 \"_\"
+      --pos-model FILE       词性标注使用的 HMM 模型
+  -D, --dict FILE            使用 FILE 代替内置词典
+  -u, --user-dict FILE       加载用户词典, 可以指定多次
+  -a, --cut-all              全模式
+  -s, --search               搜索引擎模式
+  -n, --no-hmm               不使用 HMM 识别新词
+  -j, --json                 每行输出一个 JSON 数组
+  -h, --help                 显示帮助
+  -V, --version              显示版本";
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    delimiter: String,
+    pos: bool,
+    pos_delimiter: String,
+    pos_model: Option<String>,
+    dict: Option<String>,
+    user_dicts: Vec<String>,
+    mode: CutMode,
+    hmm: bool,
+    json: bool,
+    files: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            delimiter: " / ".to_string(),
+            pos: false,
+            pos_delimiter: "_".to_string(),
+            pos_model: None,
+            dict: None,
+            user_dicts: Vec::new(),
+            mode: CutMode::Precise,
+            hmm: true,
+            json: false,
+            files: Vec::new(),
+        }
+    }
+}
+
+//解析命令行参数, 遇到 --help/--version 时返回 Ok(None)
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Option<Options>, String> {
+    let mut opts = Options::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("option {} requires an argument", name))
+        };
+        match arg.as_str() {
+            "-d" | "--delimiter" => opts.delimiter = value(&arg)?,
+            "-p" | "--pos" => opts.pos = true,
+            "--pos-delimiter" => opts.pos_delimiter = value(&arg)?,
+            "--pos-model" => opts.pos_model = Some(value(&arg)?),
+            "-D" | "--dict" => opts.dict = Some(value(&arg)?),
+            "-u" | "--user-dict" => opts.user_dicts.push(value(&arg)?),
+            "-a" | "--cut-all" => opts.mode = CutMode::Full,
+            "-s" | "--search" => opts.mode = CutMode::Search,
+            "-n" | "--no-hmm" => opts.hmm = false,
+            "-j" | "--json" => opts.json = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(None);
+            }
+            "-V" | "--version" => {
+                println!("jiebars {}", env!("CARGO_PKG_VERSION"));
+                return Ok(None);
+            }
+            "-" => opts.files.push(arg),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            _ => opts.files.push(arg),
+        }
+    }
+    if opts.pos && opts.mode != CutMode::Precise {
+        return Err("--pos only supports precise mode".to_string());
+    }
+    Ok(Some(opts))
+}
+
+fn build_jieba(opts: &Options) -> Result<Jieba, String> {
+    let mut builder = JiebaBuilder::new();
+    if let Some(dict) = &opts.dict {
+        builder = builder.dict_path(dict);
+    }
+    for user_dict in opts.user_dicts.iter() {
+        builder = builder.user_dict(user_dict);
+    }
+    if let Some(model) = &opts.pos_model {
+        builder = builder.pos_model_path(model);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+//切分一行并按选项格式化输出
+fn format_line(jieba: &Jieba, opts: &Options, line: &str) -> String {
+    if opts.pos {
+        let tags = jieba.posseg(line, opts.hmm);
+        if opts.json {
+            let items: Vec<String> = tags
+                .iter()
+                .map(|t| format!("[{},{}]", json_string(t.word), json_string(t.tag)))
+                .collect();
+            format!("[{}]", items.join(","))
+        } else {
+            let items: Vec<String> = tags
+                .iter()
+                .map(|t| format!("{}{}{}", t.word, opts.pos_delimiter, t.tag))
+                .collect();
+            items.join(&opts.delimiter)
+        }
+    } else {
+        let cut_opts = CutOptions {
+            hmm: opts.hmm,
+            ..Default::default()
+        };
+        let words = jieba.cut_with(line, opts.mode, cut_opts);
+        if opts.json {
+            let items: Vec<String> = words.iter().map(|w| json_string(w)).collect();
+            format!("[{}]", items.join(","))
+        } else {
+            words.join(&opts.delimiter)
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn process<R: BufRead, W: Write>(
+    jieba: &Jieba,
+    opts: &Options,
+    mut reader: R,
+    writer: &mut W,
+) -> io::Result<()> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let text = line.trim_end_matches(['\r', '\n']);
+        writeln!(writer, "{}", format_line(jieba, opts, text))?;
+    }
+}
+
+fn run(opts: &Options) -> Result<(), String> {
+    let jieba = build_jieba(opts)?;
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    if opts.files.is_empty() {
+        process(&jieba, opts, io::stdin().lock(), &mut writer).map_err(|e| e.to_string())?;
+    }
+    for file in opts.files.iter() {
+        let result = if file == "-" {
+            process(&jieba, opts, io::stdin().lock(), &mut writer)
+        } else {
+            let f = File::open(file).map_err(|e| format!("open {}: {}", file, e))?;
+            process(&jieba, opts, BufReader::new(f), &mut writer)
+        };
+        result.map_err(|e| format!("{}: {}", file, e))?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+fn main() {
+    let result = parse_args(std::env::args().skip(1)).and_then(|opts| match opts {
+        Some(opts) => run(&opts),
+        None => Ok(()),
+    });
+    if let Err(e) = result {
+        eprintln!("jiebars: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> impl Iterator<Item = String> + '_ {
+        s.split_whitespace().map(|x| x.to_string())
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let opts = parse_args(args("-s -n -d | -u a.txt --user-dict b.txt -j x.txt -"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(opts.mode, CutMode::Search);
+        assert!(!opts.hmm);
+        assert!(opts.json);
+        assert_eq!(opts.delimiter, "|");
+        assert_eq!(opts.user_dicts, vec!["a.txt", "b.txt"]);
+        assert_eq!(opts.files, vec!["x.txt", "-"]);
+        assert_eq!(parse_args(args("")).unwrap().unwrap(), Options::default());
+
+        assert!(parse_args(args("-d")).is_err());
+        assert!(parse_args(args("--unknown")).is_err());
+        assert!(parse_args(args("-a -p")).is_err());
+    }
+
+    #[test]
+    fn test_process() {
+        let jieba = Jieba::new().unwrap();
+        let input = "我来到北京清华大学\r\n他说\"你好\"\n";
+        let mut out = Vec::new();
+        let opts = Options::default();
+        process(&jieba, &opts, input.as_bytes(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "我 / 来到 / 北京 / 清华大学\n他 / 说 / \" / 你好 / \"\n"
+        );
+
+        let mut out = Vec::new();
+        let opts = Options {
+            json: true,
+            ..Default::default()
+        };
+        process(&jieba, &opts, input.as_bytes(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "[\"我\",\"来到\",\"北京\",\"清华大学\"]\n[\"他\",\"说\",\"\\\"\",\"你好\",\"\\\"\"]\n"
+        );
+
+        let opts = Options {
+            pos: true,
+            delimiter: " ".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_line(&jieba, &opts, "我来到北京"),
+            "我_r 来到_v 北京_ns"
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use crate::hmm;
+use crate::hmm_model::{HmmModel, DEFAULT_HMM_MODEL};
+use crate::segment::RE_HAN_DETAIL_DEFAULT;
+use regex::Regex;
+use std::sync::Arc;
+
+// 识别未登录词, 默认为 BMES HMM (HmmSegmenter)
+// fragment 是 DAG 最大概率路径上连续的、组合起来不在词典中的单字,
+// 实现需要按顺序把切分出的词放入 words, 这些词首尾相接必须正好是 fragment
+//
+// struct NameRecognizer { fallback: HmmSegmenter }
+//
+// impl OovSegmenter for NameRecognizer {
+//     fn cut<'a>(&self, fragment: &'a str, words: &mut Vec<&'a str>) {
+//         if is_name(fragment) {
+//             words.push(fragment);
+//         } else {
+//             self.fallback.cut(fragment, words);
+//         }
+//     }
+// }
+pub trait OovSegmenter: Send + Sync {
+    fn cut<'a>(&self, fragment: &'a str, words: &mut Vec<&'a str>);
+}
+
+// 使用 HmmModel 和 Viterbi 算法识别新词, 只处理汉字, 字母数字按连续的串切分
+#[derive(Debug, Clone)]
+pub struct HmmSegmenter {
+    model: Arc<HmmModel>,
+    re_han: Regex,
+}
+
+impl Default for HmmSegmenter {
+    fn default() -> Self {
+        HmmSegmenter::with_regex(DEFAULT_HMM_MODEL.clone(), RE_HAN_DETAIL_DEFAULT.clone())
+    }
+}
+
+impl HmmSegmenter {
+    pub fn new(model: HmmModel) -> HmmSegmenter {
+        HmmSegmenter::with_regex(Arc::new(model), RE_HAN_DETAIL_DEFAULT.clone())
+    }
+
+    //re_han 匹配汉字, 由 JiebaBuilder::han_ranges 生成
+    pub(crate) fn with_regex(model: Arc<HmmModel>, re_han: Regex) -> HmmSegmenter {
+        HmmSegmenter { model, re_han }
+    }
+
+    pub fn model(&self) -> &HmmModel {
+        &self.model
+    }
+}
+
+impl OovSegmenter for HmmSegmenter {
+    fn cut<'a>(&self, fragment: &'a str, words: &mut Vec<&'a str>) {
+        hmm::cut(&self.model, &self.re_han, fragment, words);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmm_segmenter() {
+        let mut words = Vec::new();
+        HmmSegmenter::default().cut("杭研", &mut words);
+        assert_eq!(words, vec!["杭研"]);
+
+        words.clear();
+        HmmSegmenter::default().cut("abc12", &mut words);
+        assert_eq!(words, vec!["abc12"]);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{CutMode, CutOptions, Jieba};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::borrow::Cow;
+
+// Jieba 只读分词, 可以在多个线程间共享
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Jieba>();
+};
+
+impl Jieba {
+    // 批量切分, 结果与输入顺序一致
+    // 开启 rayon feature 时在 rayon 线程池中并行切分, 否则依次切分. 每篇的结果与 cut_with 相同
+    pub fn cut_batch<'a>(
+        &self,
+        texts: &[&'a str],
+        mode: CutMode,
+        opts: CutOptions,
+    ) -> Vec<Vec<Cow<'a, str>>> {
+        #[cfg(feature = "rayon")]
+        let iter = texts.par_iter();
+        #[cfg(not(feature = "rayon"))]
+        let iter = texts.iter();
+        iter.map(|text| self.cut_with(text, mode, opts)).collect()
+    }
+
+    // 按行并行切分一段长文本, 与 jieba 的 enable_parallel 相同
+    // 词不会跨行, 结果与 cut_with 相同
+    pub fn cut_parallel<'a>(
+        &self,
+        text: &'a str,
+        mode: CutMode,
+        opts: CutOptions,
+    ) -> Vec<Cow<'a, str>> {
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+        self.cut_batch(&lines, mode, opts)
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cut_batch() {
+        let jieba = Jieba::new().unwrap();
+        let texts = [
+            "我来到北京清华大学",
+            "",
+            "他来到了网易杭研大厦",
+            "小明硕士毕业于中国科学院计算所",
+        ];
+        let opts = CutOptions::default();
+        let words = jieba.cut_batch(&texts, CutMode::Precise, opts);
+        assert_eq!(words.len(), texts.len());
+        for (text, words) in texts.iter().zip(words) {
+            assert_eq!(words, jieba.cut_with(text, CutMode::Precise, opts));
+        }
+        let opts = CutOptions {
+            lowercase: true,
+            ..Default::default()
+        };
+        let words = jieba.cut_batch(&["Apple iPhone"], CutMode::Precise, opts);
+        assert_eq!(words, vec![vec!["apple", " ", "iphone"]]);
+    }
+
+    #[test]
+    fn test_cut_parallel() {
+        let jieba = Jieba::new().unwrap();
+        let text =
+            "小明硕士毕业于中国科学院计算所，后在日本京都大学深造\r\n\n他来到了网易杭研大厦 iPhone!\n"
+                .repeat(10);
+        for (mode, lowercase) in [(CutMode::Precise, false), (CutMode::Search, true)] {
+            let opts = CutOptions {
+                lowercase,
+                ..Default::default()
+            };
+            assert_eq!(
+                jieba.cut_parallel(&text, mode, opts),
+                jieba.cut_with(&text, mode, opts)
+            );
+        }
+        let jieba = std::sync::Arc::new(jieba);
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let jieba = jieba.clone();
+                std::thread::spawn(move || jieba.cut("我来到北京清华大学", false, true).len())
+            })
+            .collect();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), 4);
+        }
+    }
+}
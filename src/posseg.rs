@@ -0,0 +1,665 @@
+use crate::dictionary::{DictEntry, Dictionary};
+use crate::error::{Error, JResult};
+use crate::segment::{SegmentMatches, SegmentState};
+use crate::{cut_non_words, Jieba};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+lazy_static! {
+    static ref RE_SKIP_DETAIL: Regex = Regex::new(r"([\.0-9]+|[a-zA-Z0-9]+)").unwrap();
+    static ref RE_NUM: Regex = Regex::new(r"^[\.0-9]+$").unwrap();
+    static ref RE_ENG: Regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
+    //由内置词典统计出的默认模型, 所有 Jieba 实例共享, 第一次词性标注时才生成
+    static ref DEFAULT_POS_MODEL: PosModel = PosModel::from_entries(
+        &Dictionary::default_entries().expect("embedded dict.txt should be valid")
+    );
+}
+
+const MIN_FLOAT: f64 = -3.14e100;
+
+//词语及其词性
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tag<'a> {
+    pub word: &'a str,
+    pub tag: &'a str,
+}
+
+//不在词典中的词的词性: 数字为 m, 英文为 eng, 其余为 x
+fn fallback_tag(word: &str) -> &'static str {
+    if RE_NUM.is_match(word) {
+        "m"
+    } else if RE_ENG.is_match(word) {
+        "eng"
+    } else {
+        "x"
+    }
+}
+
+// 词性标注使用的 HMM, 状态为 (B/M/E/S, 词性) 的组合, 与 jieba posseg 的 prob_start/prob_trans/prob_emit 对应
+// 文本格式每行一条记录, 概率均为对数概率:
+//   start <BMES> <tag> <prob>
+//   trans <BMES> <tag> <BMES> <tag> <prob>
+//   emit <BMES> <tag> <char> <prob>
+pub struct PosModel {
+    states: Vec<(char, String)>,
+    start: Vec<f64>,
+    trans: Vec<HashMap<usize, f64>>,
+    emit: Vec<HashMap<char, f64>>,
+    //每个字可能出现的状态, 对应 jieba 的 char_state_tab
+    char_states: HashMap<char, Vec<usize>>,
+}
+
+impl PosModel {
+    pub fn load<P: AsRef<Path>>(path: P) -> JResult<PosModel> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|e| Error::UnexpectIO(format!("open {}", path.display()), e))?;
+        PosModel::from_reader(BufReader::new(file))
+    }
+
+    fn empty() -> PosModel {
+        PosModel {
+            states: Vec::new(),
+            start: Vec::new(),
+            trans: Vec::new(),
+            emit: Vec::new(),
+            char_states: HashMap::new(),
+        }
+    }
+
+    // 用词典的词频和词性统计模型, 每个词按字标注为 (B/M/E/S, 词性), 以词频为权重计数.
+    // 词典中没有相邻词的信息, 一个词结束后转移到下一个词开头的概率取各开头状态的初始概率
+    pub(crate) fn from_entries(entries: &[DictEntry]) -> PosModel {
+        let mut model = PosModel::empty();
+        let mut index: HashMap<(char, String), usize> = HashMap::new();
+        let mut start: HashMap<usize, f64> = HashMap::new();
+        let mut trans: HashMap<(usize, usize), f64> = HashMap::new();
+        let mut emit: HashMap<(usize, char), f64> = HashMap::new();
+        for entry in entries {
+            let (freq, tag) = match (entry.freq, &entry.tag) {
+                (Some(f), Some(t)) if f > 0f64 => (f, t),
+                _ => continue,
+            };
+            let chars: Vec<char> = entry.word.chars().collect();
+            let mut prev: Option<usize> = None;
+            for (i, c) in chars.iter().enumerate() {
+                let status = match (i, chars.len()) {
+                    (_, 1) => "S",
+                    (0, _) => "B",
+                    (i, len) if i == len - 1 => "E",
+                    _ => "M",
+                };
+                let s = match model.state(&mut index, status, tag) {
+                    Some(s) => s,
+                    None => break,
+                };
+                match prev {
+                    Some(p) => *trans.entry((p, s)).or_insert(0f64) += freq,
+                    None => *start.entry(s).or_insert(0f64) += freq,
+                }
+                *emit.entry((s, *c)).or_insert(0f64) += freq;
+                prev = Some(s);
+            }
+        }
+
+        let start_total: f64 = start.values().sum();
+        for (s, count) in start.iter() {
+            model.start[*s] = (count / start_total).ln();
+        }
+        let mut trans_total = vec![0f64; model.states.len()];
+        for ((s0, _), count) in trans.iter() {
+            trans_total[*s0] += count;
+        }
+        for ((s0, s1), count) in trans {
+            model.trans[s0].insert(s1, (count / trans_total[s0]).ln());
+        }
+        let starts: Vec<(usize, f64)> = start.keys().map(|s| (*s, model.start[*s])).collect();
+        for (s, (status, _)) in model.states.iter().enumerate() {
+            if *status == 'E' || *status == 'S' {
+                model.trans[s].extend(starts.iter().cloned());
+            }
+        }
+        let mut emit_total = vec![0f64; model.states.len()];
+        for ((s, _), count) in emit.iter() {
+            emit_total[*s] += count;
+        }
+        for ((s, c), count) in emit {
+            model.emit[s].insert(c, (count / emit_total[s]).ln());
+            model.char_states.entry(c).or_default().push(s);
+        }
+        for states in model.char_states.values_mut() {
+            states.sort_unstable();
+        }
+        model
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> JResult<PosModel> {
+        let mut model = PosModel::empty();
+        let mut index: HashMap<(char, String), usize> = HashMap::new();
+        for (i, res_line) in reader.lines().enumerate() {
+            let line = res_line?;
+            let elem = line.split_whitespace().collect::<Vec<&str>>();
+            if elem.is_empty() || elem[0].starts_with('#') {
+                continue;
+            }
+            let invalid = || Error::InvalidModelEntry(i + 1, line.to_string());
+            let prob = elem
+                .last()
+                .and_then(|p| p.parse::<f64>().ok())
+                .ok_or_else(invalid)?;
+            match (elem[0], elem.len()) {
+                ("start", 4) => {
+                    let s = model
+                        .state(&mut index, elem[1], elem[2])
+                        .ok_or_else(invalid)?;
+                    model.start[s] = prob;
+                }
+                ("trans", 6) => {
+                    let s0 = model
+                        .state(&mut index, elem[1], elem[2])
+                        .ok_or_else(invalid)?;
+                    let s1 = model
+                        .state(&mut index, elem[3], elem[4])
+                        .ok_or_else(invalid)?;
+                    model.trans[s0].insert(s1, prob);
+                }
+                ("emit", 5) => {
+                    let s = model
+                        .state(&mut index, elem[1], elem[2])
+                        .ok_or_else(invalid)?;
+                    let mut cs = elem[3].chars();
+                    let c = match (cs.next(), cs.next()) {
+                        (Some(c), None) => c,
+                        _ => return Err(invalid()),
+                    };
+                    model.emit[s].insert(c, prob);
+                    model.char_states.entry(c).or_default().push(s);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        if model.states.is_empty() {
+            return Err(Error::Unexpected("empty pos model".to_string()));
+        }
+        Ok(model)
+    }
+
+    //状态在表中的下标, 第一次出现时加入状态表
+    fn state(
+        &mut self,
+        index: &mut HashMap<(char, String), usize>,
+        status: &str,
+        tag: &str,
+    ) -> Option<usize> {
+        let status = match status {
+            "B" | "M" | "E" | "S" => status.chars().next().unwrap(),
+            _ => return None,
+        };
+        let key = (status, tag.to_string());
+        if let Some(idx) = index.get(&key) {
+            return Some(*idx);
+        }
+        let idx = self.states.len();
+        self.states.push(key.clone());
+        self.start.push(MIN_FLOAT);
+        self.trans.push(HashMap::new());
+        self.emit.push(HashMap::new());
+        index.insert(key, idx);
+        Some(idx)
+    }
+
+    // 与 jieba posseg 的 viterbi 相同, 每一步只考虑该字可能出现的状态
+    fn viterbi(&self, obs: &[char]) -> Vec<usize> {
+        let all_states: Vec<usize> = (0..self.states.len()).collect();
+        let mut v: Vec<HashMap<usize, f64>> = Vec::with_capacity(obs.len());
+        let mut path: Vec<HashMap<usize, usize>> = Vec::with_capacity(obs.len());
+
+        let mut v0 = HashMap::new();
+        for y in self.char_states.get(&obs[0]).unwrap_or(&all_states) {
+            let em = self.emit[*y].get(&obs[0]).cloned().unwrap_or(MIN_FLOAT);
+            v0.insert(*y, self.start[*y] + em);
+        }
+        v.push(v0);
+        path.push(HashMap::new());
+
+        for t in 1..obs.len() {
+            let prev_states: Vec<usize> = v[t - 1]
+                .keys()
+                .filter(|x| !self.trans[**x].is_empty())
+                .cloned()
+                .collect();
+            let mut expect_next: Vec<usize> = prev_states
+                .iter()
+                .flat_map(|x| self.trans[*x].keys().cloned())
+                .collect();
+            expect_next.sort_unstable();
+            expect_next.dedup();
+            let mut obs_states: Vec<usize> = self
+                .char_states
+                .get(&obs[t])
+                .unwrap_or(&all_states)
+                .iter()
+                .filter(|y| expect_next.binary_search(y).is_ok())
+                .cloned()
+                .collect();
+            if obs_states.is_empty() {
+                obs_states = if expect_next.is_empty() {
+                    all_states.clone()
+                } else {
+                    expect_next
+                };
+            }
+            let mut vt = HashMap::new();
+            let mut pt = HashMap::new();
+            for y in obs_states {
+                let em = self.emit[y].get(&obs[t]).cloned().unwrap_or(MIN_FLOAT);
+                let best = prev_states
+                    .iter()
+                    .map(|y0| {
+                        let tr = self.trans[*y0]
+                            .get(&y)
+                            .cloned()
+                            .unwrap_or(f64::NEG_INFINITY);
+                        (v[t - 1][y0] + tr + em, *y0)
+                    })
+                    .max_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                if let Some((prob, y0)) = best {
+                    vt.insert(y, prob);
+                    pt.insert(y, y0);
+                }
+            }
+            if vt.is_empty() {
+                //没有任何可以转移的状态, 从该字重新开始
+                for y in self.char_states.get(&obs[t]).unwrap_or(&all_states) {
+                    let em = self.emit[*y].get(&obs[t]).cloned().unwrap_or(MIN_FLOAT);
+                    vt.insert(*y, self.start[*y] + em);
+                }
+            }
+            v.push(vt);
+            path.push(pt);
+        }
+
+        let best = |vt: &HashMap<usize, f64>| {
+            vt.iter()
+                .map(|(y, p)| (*p, *y))
+                .max_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+                .map(|(_, y)| y)
+                .unwrap_or(0)
+        };
+        let mut state = best(&v[obs.len() - 1]);
+        let mut route = vec![0usize; obs.len()];
+        for t in (0..obs.len()).rev() {
+            route[t] = state;
+            if t > 0 {
+                state = match path[t].get(&state) {
+                    Some(prev) => *prev,
+                    None => best(&v[t - 1]),
+                };
+            }
+        }
+        route
+    }
+
+    //对一段连续汉字同时分词和标注词性
+    fn cut_han<'a>(&'a self, sentence: &'a str, tags: &mut Vec<Tag<'a>>) {
+        let chars: Vec<char> = sentence.chars().collect();
+        let byte_index: Vec<usize> = sentence
+            .char_indices()
+            .map(|x| x.0)
+            .chain(std::iter::once(sentence.len()))
+            .collect();
+        let route = self.viterbi(&chars);
+        //next 之前的字已经输出. 模型中没有合法转移时路径可能不完整, 例如 B 后面直接是 S,
+        //此时把未结束的部分作为一个词输出, 保证所有的字都会输出
+        let mut next = 0usize;
+        let push = |begin: usize, end: usize, tags: &mut Vec<Tag<'a>>| {
+            tags.push(Tag {
+                word: &sentence[byte_index[begin]..byte_index[end]],
+                tag: &self.states[route[begin]].1,
+            });
+        };
+        for (i, s) in route.iter().enumerate() {
+            match self.states[*s].0 {
+                'B' => {
+                    if next < i {
+                        push(next, i, tags);
+                    }
+                    next = i;
+                }
+                'E' => {
+                    push(next, i + 1, tags);
+                    next = i + 1;
+                }
+                'S' => {
+                    if next < i {
+                        push(next, i, tags);
+                    }
+                    push(i, i + 1, tags);
+                    next = i + 1;
+                }
+                _ => {}
+            }
+        }
+        if next < chars.len() {
+            push(next, chars.len(), tags);
+        }
+    }
+
+    fn cut<'a>(&'a self, re_han: &Regex, sentence: &'a str, tags: &mut Vec<Tag<'a>>) {
+        for state in SegmentMatches::new(re_han, sentence) {
+            match state {
+                SegmentState::Matched(m) => self.cut_han(m.as_str(), tags),
+                SegmentState::Unmatched(s) => {
+                    for x in SegmentMatches::new(&RE_SKIP_DETAIL, s) {
+                        let word = x.into_str();
+                        if !word.is_empty() {
+                            tags.push(Tag {
+                                word,
+                                tag: fallback_tag(word),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Jieba {
+    //设置词性标注使用的 HMM, 默认使用由内置词典统计出的模型
+    pub fn set_pos_model(&mut self, model: PosModel) {
+        self.pos_model = Some(model);
+    }
+
+    //分词并标注词性, 词典中的词使用词典中的词性
+    pub fn posseg<'a>(&'a self, text: &'a str, hmm: bool) -> Vec<Tag<'a>> {
+        let mut tags: Vec<Tag> = Vec::new();
+        let mut words: Vec<&str> = Vec::new();
+        for m in SegmentMatches::new(&self.re_han, text) {
+            words.clear();
+            match m {
+                SegmentState::Matched(m) => {
+                    if hmm {
+                        self.posseg_dag_with_hmm(m.as_str(), &mut tags);
+                        continue;
+                    }
+                    self.cut_dag_no_hmm(m.as_str(), &mut words);
+                }
+                SegmentState::Unmatched(s) => cut_non_words(s, self.cut_options(hmm), &mut words),
+            }
+            for word in words.iter() {
+                tags.push(self.tag_word(word));
+            }
+        }
+        tags
+    }
+
+    fn tag_word<'a>(&'a self, word: &'a str) -> Tag<'a> {
+        let tag = match self.dict.frequency(word) {
+            Some(f) if f > 0f64 => self.dict.tag(word),
+            _ => None,
+        };
+        Tag {
+            word,
+            tag: tag.unwrap_or_else(|| fallback_tag(word)),
+        }
+    }
+
+    fn posseg_dag_with_hmm<'a>(&'a self, sentence: &'a str, tags: &mut Vec<Tag<'a>>) {
+        let rs = self.calc(sentence);
+        let mut x = 0usize;
+        let mut left: Option<usize> = None;
+        while x < sentence.len() {
+            let y = rs[x].1;
+            let frag = &sentence[x..y];
+            if frag.chars().count() == 1 {
+                if left.is_none() {
+                    left = Some(x);
+                }
+            } else {
+                if let Some(l) = left {
+                    self.posseg_single_chars(&sentence[l..x], tags);
+                    left = None;
+                }
+                tags.push(self.tag_word(frag));
+            }
+            x = y;
+        }
+        if let Some(l) = left {
+            self.posseg_single_chars(&sentence[l..], tags);
+        }
+    }
+
+    fn posseg_single_chars<'a>(&'a self, word: &'a str, tags: &mut Vec<Tag<'a>>) {
+        let f = self.dict.frequency(word);
+        if word.chars().count() == 1 || (f.is_some() && f != Some(0.0)) {
+            let mut words: Vec<&str> = Vec::new();
+            self.cut_single_chars(word, &mut words);
+            tags.extend(words.into_iter().map(|w| self.tag_word(w)));
+            return;
+        }
+        let start = tags.len();
+        let model = self.pos_model.as_ref().unwrap_or(&DEFAULT_POS_MODEL);
+        model.cut(&self.re_han_detail, word, tags);
+        //被删除的词不能再由 HMM 组合出来
+        if tags[start..]
+            .iter()
+            .any(|t| self.dict.is_force_split(t.word))
+        {
+            for t in tags.split_off(start) {
+                if self.dict.is_force_split(t.word) {
+                    let mut chars: Vec<&str> = Vec::new();
+                    crate::push_chars(t.word, &mut chars);
+                    tags.extend(chars.into_iter().map(|w| self.tag_word(w)));
+                } else {
+                    tags.push(t);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::segment::RE_HAN_DETAIL_DEFAULT;
+
+    static TEST_MODEL: &str = "
+start B nr -0.5
+start S r -1.0
+start B v -1.5
+trans B nr E nr -0.1
+trans B nr M nr -2.3
+trans M nr E nr -0.1
+trans E nr B v -0.7
+trans E nr S r -0.7
+trans S r B nr -0.7
+trans S r B v -0.7
+trans B v E v -0.1
+trans E v S r -0.7
+trans E v B nr -0.7
+emit B nr 王 -1.0
+emit M nr 小 -1.0
+emit E nr 明 -1.0
+emit S r 他 -0.1
+emit B v 来 -1.0
+emit E v 到 -1.0
+";
+
+    #[test]
+    fn test_pos_model() {
+        let model = PosModel::from_reader(TEST_MODEL.as_bytes()).unwrap();
+        let mut tags: Vec<Tag> = Vec::new();
+        model.cut(&RE_HAN_DETAIL_DEFAULT, "他王小明来到", &mut tags);
+        assert_eq!(
+            tags,
+            vec![
+                Tag {
+                    word: "他",
+                    tag: "r"
+                },
+                Tag {
+                    word: "王小明",
+                    tag: "nr"
+                },
+                Tag {
+                    word: "来到",
+                    tag: "v"
+                },
+            ]
+        );
+        assert!(PosModel::from_reader("emit X nr 王 -1.0".as_bytes()).is_err());
+        assert!(PosModel::from_reader("start B nr".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_posseg() {
+        let mut jieba = Jieba::new().unwrap();
+        let tags = jieba.posseg("我爱北京天安门, iPhone 12", true);
+        assert_eq!(
+            &tags[..2],
+            &[
+                Tag {
+                    word: "我",
+                    tag: "r"
+                },
+                Tag {
+                    word: "爱",
+                    tag: "v"
+                }
+            ]
+        );
+        assert!(tags.contains(&Tag {
+            word: "北京",
+            tag: "ns"
+        }));
+        assert!(tags.contains(&Tag {
+            word: "iPhone",
+            tag: "eng"
+        }));
+        assert!(tags.contains(&Tag {
+            word: "12",
+            tag: "m"
+        }));
+        assert!(tags.contains(&Tag {
+            word: ",",
+            tag: "x"
+        }));
+
+        let tags = jieba.posseg("他来到了网易杭研大厦", false);
+        let words: Vec<&str> = tags.iter().map(|t| t.word).collect();
+        assert_eq!(words, jieba.cut("他来到了网易杭研大厦", false, false));
+        assert_eq!(
+            tags[0],
+            Tag {
+                word: "他",
+                tag: "r"
+            }
+        );
+
+        jieba.add_word("杭研大厦", None, Some("nt")).unwrap();
+        let tags = jieba.posseg("他来到了网易杭研大厦", true);
+        assert_eq!(
+            tags.last(),
+            Some(&Tag {
+                word: "杭研大厦",
+                tag: "nt"
+            })
+        );
+    }
+
+    #[test]
+    fn test_posseg_with_model() {
+        let mut jieba = Jieba::new().unwrap();
+        jieba.set_pos_model(PosModel::from_reader(TEST_MODEL.as_bytes()).unwrap());
+        let tags = jieba.posseg("他说王小明来到", true);
+        let words: Vec<&str> = tags.iter().map(|t| t.word).collect();
+        assert_eq!(words.concat(), "他说王小明来到");
+        assert_eq!(tags.last(), Some(&Tag { word: "来到", tag: "v" }));
+    }
+
+    #[test]
+    fn test_posseg_keeps_all_chars() {
+        //B 后面直接是 S 时, B 对应的字也要输出
+        let model = "start B nr -0.1\nemit B nr 王 -0.1\nstart S r -0.1\nemit S r 他 -0.1\n";
+        let jieba = Jieba::builder()
+            .empty_dict()
+            .pos_model(PosModel::from_reader(model.as_bytes()).unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(
+            jieba.posseg("王他", true),
+            vec![
+                Tag {
+                    word: "王",
+                    tag: "nr"
+                },
+                Tag {
+                    word: "他",
+                    tag: "r"
+                }
+            ]
+        );
+
+        //任意模型 (包括不合法的转移) 输出的词拼起来都等于原文
+        let chars: Vec<char> = "王小明他来到了".chars().collect();
+        let mut seed = 42u64;
+        let mut rand = |n: usize| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        let status = ["B", "M", "E", "S"];
+        let tags = ["nr", "r", "v"];
+        for _ in 0..50 {
+            let mut model = String::new();
+            for _ in 0..1 + rand(8) {
+                let (s, t) = (status[rand(4)], tags[rand(3)]);
+                model += &format!("start {} {} -{}\n", s, t, rand(5));
+                let (s1, t1) = (status[rand(4)], tags[rand(3)]);
+                model += &format!("trans {} {} {} {} -{}\n", s, t, s1, t1, rand(5));
+                let c = chars[rand(chars.len())];
+                model += &format!("emit {} {} {} -{}\n", s1, t1, c, rand(5));
+            }
+            let jieba = Jieba::builder()
+                .empty_dict()
+                .pos_model(PosModel::from_reader(model.as_bytes()).unwrap())
+                .build()
+                .unwrap();
+            for _ in 0..10 {
+                let text: String = (0..1 + rand(8)).map(|_| chars[rand(chars.len())]).collect();
+                let words: Vec<&str> = jieba.posseg(&text, true).iter().map(|t| t.word).collect();
+                assert_eq!(words.concat(), text, "model:\n{}", model);
+            }
+        }
+    }
+
+    #[test]
+    fn test_default_pos_model() {
+        //不在词典中的人名由默认模型识别并标注
+        let jieba = Jieba::new().unwrap();
+        let tags = jieba.posseg("王小二在吃饭", true);
+        assert_eq!(
+            tags[0],
+            Tag {
+                word: "王小二",
+                tag: "nr"
+            }
+        );
+        let tags = jieba.posseg("小明硕士毕业于中国科学院计算所", true);
+        assert_eq!(
+            tags[0],
+            Tag {
+                word: "小明",
+                tag: "nr"
+            }
+        );
+        assert!(tags.iter().all(|t| t.tag != "x"));
+    }
+}
@@ -1,9 +1,49 @@
 use lazy_static::lazy_static;
 use regex::{Match, Matches, Regex};
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+
+// 默认的汉字范围: CJK 统一汉字、扩展 A 到扩展 J 以及兼容汉字
+pub static DEFAULT_HAN_RANGES: &[RangeInclusive<char>] = &[
+    '\u{3400}'..='\u{4DBF}',
+    '\u{4E00}'..='\u{9FFF}',
+    '\u{F900}'..='\u{FAFF}',
+    '\u{20000}'..='\u{2A6DF}',
+    '\u{2A700}'..='\u{2EE5F}',
+    '\u{2F800}'..='\u{2FA1F}',
+    '\u{30000}'..='\u{3347F}',
+];
+
+//jieba 原来使用的汉字范围
+pub static LEGACY_HAN_RANGES: &[RangeInclusive<char>] = &['\u{4E00}'..='\u{9FD5}'];
+
+//汉字范围对应的正则字符类, 不包括两边的方括号
+pub(crate) fn han_class(ranges: &[RangeInclusive<char>]) -> String {
+    let mut class = String::new();
+    for r in ranges {
+        let _ = write!(
+            class,
+            "\\x{{{:X}}}-\\x{{{:X}}}",
+            *r.start() as u32,
+            *r.end() as u32
+        );
+    }
+    class
+}
+
+//需要分词的文本: 汉字、字母、数字以及 +#&._%-
+pub(crate) fn han_regex(ranges: &[RangeInclusive<char>]) -> Regex {
+    Regex::new(&format!("([{}a-zA-Z0-9+#&\\._%\\-]+)", han_class(ranges))).unwrap()
+}
+
+//只包含汉字, HMM 只处理这部分文本
+pub(crate) fn han_detail_regex(ranges: &[RangeInclusive<char>]) -> Regex {
+    Regex::new(&format!("([{}]+)", han_class(ranges))).unwrap()
+}
 
 lazy_static! {
-    pub(crate) static ref RE_HAN_DEFAULT: Regex =
-        Regex::new(r"([\u4E00-\u9FD5a-zA-Z0-9+#&\._%\-]+)").unwrap();
+    pub(crate) static ref RE_HAN_DEFAULT: Regex = han_regex(DEFAULT_HAN_RANGES);
+    pub(crate) static ref RE_HAN_DETAIL_DEFAULT: Regex = han_detail_regex(DEFAULT_HAN_RANGES);
     pub(crate) static ref RE_SKIP_DEAFULT: Regex = Regex::new(r"(\r\n|\s)").unwrap();
     pub(crate) static ref RE_SKIP_CUT_ALL: Regex = Regex::new(r"[^[:alnum:]+#\n]").unwrap();
 }
@@ -19,7 +59,7 @@ impl<'r, 't> SegmentMatches<'r, 't> {
     pub fn new(re: &'r Regex, text: &'t str) -> SegmentMatches<'r, 't> {
         SegmentMatches {
             matches: re.find_iter(text),
-            text: text,
+            text,
             last: 0,
             matched: None,
         }
@@ -93,6 +133,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_han_ranges() {
+        for c in [
+            '中',
+            '\u{9FD6}',
+            '\u{9FFF}',
+            '䶮',
+            '\u{20000}',
+            '\u{2B740}',
+            '\u{31350}',
+        ] {
+            assert!(RE_HAN_DETAIL_DEFAULT.is_match(&c.to_string()), "{:?}", c);
+        }
+        for c in ['a', '，', '々', '👪'] {
+            assert!(!RE_HAN_DETAIL_DEFAULT.is_match(&c.to_string()), "{:?}", c);
+        }
+        let legacy = han_detail_regex(LEGACY_HAN_RANGES);
+        assert!(legacy.is_match("中"));
+        assert!(!legacy.is_match("\u{9FD6}"));
+    }
+
     #[test]
     fn test_re_skip_cut_all() {
         let seg = SegmentMatches::new(&RE_SKIP_CUT_ALL, "I have two match the");
@@ -0,0 +1,303 @@
+use crate::error::{Error, JResult};
+use crate::{CutMode, CutOptions, Jieba};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// 语料统计: 词频、文档频率、相邻词对的次数和各词性的次数
+// 不含汉字、字母和数字的标点、空白、. - _ % & 等符号不计数, 并且会切断相邻词对
+//
+// let mut stats = CorpusStats::new();
+// stats.set_pos(true);
+// stats.add_documents(&jieba, &docs);
+// stats.save_dict("user_dict.txt", 5)?;
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorpusStats {
+    hmm: bool,
+    pos: bool,
+    documents: u64,
+    total_words: u64,
+    words: HashMap<String, u64>,
+    doc_freq: HashMap<String, u64>,
+    bigrams: HashMap<(String, String), u64>,
+    pos_counts: HashMap<String, u64>,
+    word_tags: HashMap<(String, String), u64>,
+}
+
+impl Default for CorpusStats {
+    fn default() -> Self {
+        CorpusStats::new()
+    }
+}
+
+impl CorpusStats {
+    pub fn new() -> CorpusStats {
+        CorpusStats {
+            hmm: true,
+            pos: false,
+            documents: 0,
+            total_words: 0,
+            words: HashMap::new(),
+            doc_freq: HashMap::new(),
+            bigrams: HashMap::new(),
+            pos_counts: HashMap::new(),
+            word_tags: HashMap::new(),
+        }
+    }
+
+    //是否使用 HMM 识别新词, 默认使用
+    pub fn set_hmm(&mut self, hmm: bool) {
+        self.hmm = hmm;
+    }
+
+    //是否使用 posseg 统计词性, 默认不统计. 统计词性时导出的词典带有每个词最常见的词性
+    pub fn set_pos(&mut self, pos: bool) {
+        self.pos = pos;
+    }
+
+    //设置相同、计数为空的 CorpusStats
+    #[cfg(feature = "rayon")]
+    fn empty_like(&self) -> CorpusStats {
+        CorpusStats {
+            hmm: self.hmm,
+            pos: self.pos,
+            ..CorpusStats::new()
+        }
+    }
+
+    //切分一篇文档并计数
+    pub fn add_document(&mut self, jieba: &Jieba, text: &str) {
+        let tokens: Vec<(&str, Option<&str>)> = if self.pos {
+            jieba
+                .posseg(text, self.hmm)
+                .into_iter()
+                .map(|t| (t.word, Some(t.tag)))
+                .collect()
+        } else {
+            let opts = CutOptions {
+                hmm: self.hmm,
+                ..Default::default()
+            };
+            jieba
+                .cut_words(text, CutMode::Precise, opts)
+                .into_iter()
+                .map(|w| (w, None))
+                .collect()
+        };
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut prev: Option<&str> = None;
+        for (word, tag) in tokens {
+            if !jieba.is_word(word) {
+                prev = None;
+                continue;
+            }
+            self.total_words += 1;
+            *self.words.entry(word.to_string()).or_insert(0) += 1;
+            if seen.insert(word) {
+                *self.doc_freq.entry(word.to_string()).or_insert(0) += 1;
+            }
+            if let Some(p) = prev {
+                *self
+                    .bigrams
+                    .entry((p.to_string(), word.to_string()))
+                    .or_insert(0) += 1;
+            }
+            if let Some(tag) = tag {
+                *self.pos_counts.entry(tag.to_string()).or_insert(0) += 1;
+                *self
+                    .word_tags
+                    .entry((word.to_string(), tag.to_string()))
+                    .or_insert(0) += 1;
+            }
+            prev = Some(word);
+        }
+        self.documents += 1;
+    }
+
+    // 统计多篇文档, 开启 rayon feature 时每个线程各自计数后合并
+    pub fn add_documents(&mut self, jieba: &Jieba, docs: &[&str]) {
+        #[cfg(feature = "rayon")]
+        {
+            let stats = docs
+                .par_iter()
+                .fold(
+                    || self.empty_like(),
+                    |mut stats, doc| {
+                        stats.add_document(jieba, doc);
+                        stats
+                    },
+                )
+                .reduce(
+                    || self.empty_like(),
+                    |mut a, b| {
+                        a.merge(b);
+                        a
+                    },
+                );
+            self.merge(stats);
+        }
+        #[cfg(not(feature = "rayon"))]
+        for doc in docs {
+            self.add_document(jieba, doc);
+        }
+    }
+
+    //合并另一个 CorpusStats 的计数, 用于在多个线程中分别统计
+    pub fn merge(&mut self, other: CorpusStats) {
+        self.documents += other.documents;
+        self.total_words += other.total_words;
+        merge_counts(&mut self.words, other.words);
+        merge_counts(&mut self.doc_freq, other.doc_freq);
+        merge_counts(&mut self.bigrams, other.bigrams);
+        merge_counts(&mut self.pos_counts, other.pos_counts);
+        merge_counts(&mut self.word_tags, other.word_tags);
+    }
+
+    pub fn documents(&self) -> u64 {
+        self.documents
+    }
+
+    pub fn total_words(&self) -> u64 {
+        self.total_words
+    }
+
+    pub fn word_count(&self, word: &str) -> u64 {
+        self.words.get(word).copied().unwrap_or(0)
+    }
+
+    //出现过该词的文档数
+    pub fn doc_freq(&self, word: &str) -> u64 {
+        self.doc_freq.get(word).copied().unwrap_or(0)
+    }
+
+    //first 后面紧跟 second 的次数
+    pub fn bigram_count(&self, first: &str, second: &str) -> u64 {
+        self.bigrams
+            .get(&(first.to_string(), second.to_string()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn pos_count(&self, tag: &str) -> u64 {
+        self.pos_counts.get(tag).copied().unwrap_or(0)
+    }
+
+    //出现次数最多的 top_k 个词, 次数相同时按词排序
+    pub fn top_words(&self, top_k: usize) -> Vec<(&str, u64)> {
+        let mut words: Vec<(&str, u64)> =
+            self.words.iter().map(|(w, c)| (w.as_str(), *c)).collect();
+        words.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        words.truncate(top_k);
+        words
+    }
+
+    //每个词最常见的词性, 次数相同时取字典序较小的
+    fn best_tags(&self) -> HashMap<&str, (&str, u64)> {
+        let mut tags: HashMap<&str, (&str, u64)> = HashMap::new();
+        for ((word, tag), count) in self.word_tags.iter() {
+            let best = tags.entry(word.as_str()).or_insert((tag.as_str(), *count));
+            if *count > best.1 || (*count == best.1 && tag.as_str() < best.0) {
+                *best = (tag.as_str(), *count);
+            }
+        }
+        tags
+    }
+
+    // 按 dict.txt 的格式导出, 每行 `word freq [tag]`, 只导出次数不少于 min_count 的词
+    // 可以直接作为用户词典加载
+    pub fn write_dict<W: Write>(&self, writer: &mut W, min_count: u64) -> std::io::Result<()> {
+        let tags = self.best_tags();
+        for (word, count) in self.top_words(usize::MAX) {
+            if count < min_count {
+                break;
+            }
+            match tags.get(word) {
+                Some((tag, _)) => writeln!(writer, "{} {} {}", word, count, tag)?,
+                None => writeln!(writer, "{} {}", word, count)?,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save_dict<P: AsRef<Path>>(&self, path: P, min_count: u64) -> JResult<()> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .map_err(|e| Error::UnexpectIO(format!("create {}", path.display()), e))?;
+        let mut writer = BufWriter::new(file);
+        self.write_dict(&mut writer, min_count)
+            .and_then(|_| writer.flush())
+            .map_err(|e| Error::UnexpectIO(format!("write {}", path.display()), e))
+    }
+}
+
+fn merge_counts<K: std::hash::Hash + Eq>(counts: &mut HashMap<K, u64>, other: HashMap<K, u64>) {
+    for (k, c) in other {
+        *counts.entry(k).or_insert(0) += c;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static DOCS: [&str; 3] = [
+        "我来到北京清华大学",
+        "他来到了网易杭研大厦",
+        "北京欢迎你, 我来到北京",
+    ];
+
+    #[test]
+    fn test_stats() {
+        let jieba = Jieba::new().unwrap();
+        let mut stats = CorpusStats::new();
+        stats.add_documents(&jieba, &DOCS);
+        assert_eq!(stats.documents(), 3);
+        assert_eq!(stats.word_count("北京"), 3);
+        assert_eq!(stats.doc_freq("北京"), 2);
+        assert_eq!(stats.word_count(","), 0);
+        assert_eq!(stats.word_count("."), 0);
+        assert_eq!(stats.bigram_count("来到", "北京"), 2);
+        //标点切断相邻词对
+        assert_eq!(stats.bigram_count("你", "我"), 0);
+        assert_eq!(stats.top_words(2), vec![("北京", 3), ("来到", 3)]);
+        assert_eq!(stats.pos_count("ns"), 0);
+
+        let mut merged = CorpusStats::new();
+        for doc in DOCS.iter() {
+            let mut part = CorpusStats::new();
+            part.add_document(&jieba, doc);
+            merged.merge(part);
+        }
+        assert_eq!(merged, stats);
+
+        //re_han 匹配的 . - 等符号也不计数
+        let mut stats = CorpusStats::new();
+        stats.add_document(&jieba, "北京.上海 - 3.5%");
+        assert_eq!(stats.total_words(), 3);
+        assert_eq!(stats.word_count("."), 0);
+        assert_eq!(stats.word_count("-"), 0);
+        assert_eq!(stats.word_count("3.5%"), 1);
+        assert_eq!(stats.bigram_count("北京", "上海"), 0);
+    }
+
+    #[test]
+    fn test_write_dict() {
+        let mut jieba = Jieba::new().unwrap();
+        let mut stats = CorpusStats::new();
+        stats.set_pos(true);
+        stats.add_documents(&jieba, &DOCS);
+        assert_eq!(stats.pos_count("ns"), 3);
+        assert_eq!(stats.total_words(), stats.pos_counts.values().sum::<u64>());
+
+        let mut data = Vec::new();
+        stats.write_dict(&mut data, 2).unwrap();
+        let data = String::from_utf8(data).unwrap();
+        assert_eq!(data, "北京 3 ns\n来到 3 v\n我 2 r\n");
+
+        jieba.load_user_dict_from_reader(data.as_bytes()).unwrap();
+        assert_eq!(jieba.tag("北京"), Some("ns"));
+    }
+}
@@ -0,0 +1,219 @@
+use crate::error::{Error, JResult};
+use crate::segment::SegmentMatches;
+use crate::{apply_lowercase, CutMode, CutOptions, Jieba};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::BufRead;
+
+//cut_reader 每次至少读入的字节数
+const STREAM_BLOCK_SIZE: usize = 64 * 1024;
+
+//Jieba::cut_iter 返回的迭代器, 每次切分 re_han 分出的一段文本, 除 lowercase 转换过的词外都是原文的切片
+pub struct CutIter<'a> {
+    jieba: &'a Jieba,
+    blocks: SegmentMatches<'a, 'a>,
+    mode: CutMode,
+    opts: CutOptions,
+    //当前一段的切分结果
+    words: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> CutIter<'a> {
+    pub(crate) fn new(jieba: &'a Jieba, text: &'a str, mode: CutMode, opts: CutOptions) -> Self {
+        CutIter {
+            jieba,
+            blocks: SegmentMatches::new(&jieba.re_han, text),
+            mode,
+            opts,
+            words: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for CutIter<'a> {
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Cow<'a, str>> {
+        while self.pos == self.words.len() {
+            self.words.clear();
+            self.pos = 0;
+            let block = self.blocks.next()?;
+            self.jieba
+                .cut_block(block, self.mode, self.opts, &mut self.words);
+        }
+        let word = self.words[self.pos];
+        self.pos += 1;
+        Some(apply_lowercase(word, self.opts))
+    }
+}
+
+// Jieba::cut_reader 返回的迭代器, 每次读入约 STREAM_BLOCK_SIZE 字节并在不会切断词的位置切开:
+// 优先在最后一个空白之后, 其次在最后一段 re_han 匹配的文本之前.
+// 只有一段 re_han 匹配的文本超过 STREAM_BLOCK_SIZE 时才会强制切开
+pub struct CutReader<'a, R> {
+    jieba: &'a Jieba,
+    reader: R,
+    mode: CutMode,
+    opts: CutOptions,
+    block_size: usize,
+    buf: Vec<u8>,
+    words: VecDeque<String>,
+    done: bool,
+}
+
+impl<'a, R: BufRead> CutReader<'a, R> {
+    pub(crate) fn new(jieba: &'a Jieba, reader: R, mode: CutMode, opts: CutOptions) -> Self {
+        CutReader {
+            jieba,
+            reader,
+            mode,
+            opts,
+            block_size: STREAM_BLOCK_SIZE,
+            buf: Vec::new(),
+            words: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    //读入下一段并切分
+    fn fill(&mut self) -> JResult<()> {
+        let eof = loop {
+            if self.buf.len() >= self.block_size {
+                break false;
+            }
+            let chunk = self
+                .reader
+                .fill_buf()
+                .map_err(|e| Error::UnexpectIO("read".to_string(), e))?;
+            if chunk.is_empty() {
+                break true;
+            }
+            let n = chunk.len();
+            self.buf.extend_from_slice(chunk);
+            self.reader.consume(n);
+        };
+        let end = if eof {
+            self.done = true;
+            self.buf.len()
+        } else {
+            self.split_point()
+        };
+        let text = std::str::from_utf8(&self.buf[..end])
+            .map_err(|e| Error::Unexpected(format!("invalid utf-8: {}", e)))?;
+        for word in self.jieba.cut_with(text, self.mode, self.opts) {
+            self.words.push_back(word.into_owned());
+        }
+        self.buf.drain(..end);
+        Ok(())
+    }
+
+    fn split_point(&self) -> usize {
+        //ASCII 字节不会出现在多字节字符中间, 不在 \r 之后切开以免拆开 \r\n
+        if let Some(i) = self
+            .buf
+            .iter()
+            .rposition(|b| matches!(b, b'\n' | b' ' | b'\t' | b'\x0c'))
+        {
+            return i + 1;
+        }
+        let valid = match std::str::from_utf8(&self.buf) {
+            Ok(s) => s.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        if valid == 0 {
+            return self.buf.len();
+        }
+        let text = std::str::from_utf8(&self.buf[..valid]).unwrap_or_default();
+        match self.jieba.re_han.find_iter(text).last() {
+            Some(m) if m.end() < valid => m.end(),
+            Some(m) if m.start() > 0 => m.start(),
+            _ => valid,
+        }
+    }
+}
+
+impl<'a, R: BufRead> Iterator for CutReader<'a, R> {
+    type Item = JResult<String>;
+
+    fn next(&mut self) -> Option<JResult<String>> {
+        loop {
+            if let Some(word) = self.words.pop_front() {
+                return Some(Ok(word));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.fill() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cut_iter() {
+        let jieba = Jieba::new().unwrap();
+        let text = "小明硕士毕业于中国科学院计算所，后在日本京都大学深造\n\n他来到了网易杭研大厦!";
+        for mode in [
+            CutMode::Full,
+            CutMode::Precise,
+            CutMode::Search,
+            CutMode::SearchFine,
+        ] {
+            for hmm in [false, true] {
+                let opts = CutOptions {
+                    hmm,
+                    ..Default::default()
+                };
+                let words: Vec<Cow<str>> = jieba.cut_iter(text, mode, opts).collect();
+                assert_eq!(words, jieba.cut_with(text, mode, opts));
+            }
+        }
+        let opts = CutOptions {
+            lowercase: true,
+            ..Default::default()
+        };
+        let text = "后在 Apple iPhone 深造";
+        let words: Vec<Cow<str>> = jieba.cut_iter(text, CutMode::Precise, opts).collect();
+        assert_eq!(words, jieba.cut_with(text, CutMode::Precise, opts));
+        assert!(words.contains(&Cow::Borrowed("apple")));
+        let opts = CutOptions {
+            keep_whitespace: false,
+            keep_punctuation: false,
+            ..Default::default()
+        };
+        let mut iter = jieba.cut_iter("，，你好", CutMode::Precise, opts);
+        assert_eq!(iter.next().as_deref(), Some("你好"));
+        assert_eq!(iter.next(), None);
+        assert_eq!(jieba.cut_iter("", CutMode::Precise, opts).count(), 0);
+    }
+
+    #[test]
+    fn test_cut_reader() {
+        let jieba = Jieba::new().unwrap();
+        let text = "小明硕士毕业于中国科学院计算所，后在日本京都大学深造\r\n\
+                    他来到了网易杭研大厦!\n我来到北京清华大学 Apple iPhone 12\n"
+            .repeat(3);
+        let opts = CutOptions::default();
+        let expected = jieba.cut_with(&text, CutMode::Precise, opts);
+        for block_size in [48, 50, 64, 100, STREAM_BLOCK_SIZE] {
+            let reader = std::io::BufReader::with_capacity(5, text.as_bytes());
+            let mut iter = jieba.cut_reader(reader, CutMode::Precise, opts);
+            iter.block_size = block_size;
+            let words: Vec<String> = iter.map(|w| w.unwrap()).collect();
+            assert_eq!(words, expected);
+        }
+
+        let reader = &b"hello\n\xff\xfe"[..];
+        let results: Vec<JResult<String>> =
+            jieba.cut_reader(reader, CutMode::Precise, opts).collect();
+        assert!(results.last().unwrap().is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{Jieba, TokenizeMode};
+use std::sync::Arc;
+use tantivy_tokenizer_api::{Token, TokenStream, Tokenizer};
+
+// tantivy 分词器, 在 tantivy 中注册后即可用于中文字段:
+//
+// let tokenizer = JiebaTokenizer::new(Arc::new(Jieba::new()?), TokenizeMode::Search);
+// index.tokenizers().register("jieba", tokenizer);
+//
+// 空白和标点不会输出, 搜索引擎模式下长词中的 2 字词和 3 字词与长词位于同一个 position
+#[derive(Clone)]
+pub struct JiebaTokenizer {
+    jieba: Arc<Jieba>,
+    mode: TokenizeMode,
+    hmm: bool,
+}
+
+impl JiebaTokenizer {
+    pub fn new(jieba: Arc<Jieba>, mode: TokenizeMode) -> JiebaTokenizer {
+        JiebaTokenizer {
+            jieba,
+            mode,
+            hmm: true,
+        }
+    }
+
+    //是否使用 HMM 识别新词, 默认使用
+    pub fn set_hmm(&mut self, hmm: bool) {
+        self.hmm = hmm;
+    }
+}
+
+pub struct JiebaTokenStream {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Tokenizer for JiebaTokenizer {
+    type TokenStream<'a> = JiebaTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> JiebaTokenStream {
+        let mut tokens = Vec::new();
+        let mut grams: Vec<&str> = Vec::new();
+        let words = self.jieba.tokenize(text, TokenizeMode::Default, self.hmm);
+        let words = words.iter().filter(|w| self.jieba.is_word(w.word));
+        for (position, word) in words.enumerate() {
+            if self.mode == TokenizeMode::Search {
+                grams.clear();
+                self.jieba.cut_grams(word.word, &mut grams);
+                for gram in grams.iter() {
+                    let start = word.start + (gram.as_ptr() as usize - word.word.as_ptr() as usize);
+                    tokens.push(Token {
+                        offset_from: start,
+                        offset_to: start + gram.len(),
+                        position,
+                        text: gram.to_string(),
+                        position_length: 1,
+                    });
+                }
+            }
+            tokens.push(Token {
+                offset_from: word.start,
+                offset_to: word.end,
+                position,
+                text: word.word.to_string(),
+                position_length: 1,
+            });
+        }
+        JiebaTokenStream { tokens, index: 0 }
+    }
+}
+
+impl TokenStream for JiebaTokenStream {
+    fn advance(&mut self) -> bool {
+        if self.index < self.tokens.len() {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.index - 1]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.index - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(tokenizer: &mut JiebaTokenizer, text: &str) -> Vec<Token> {
+        let mut stream = tokenizer.token_stream(text);
+        let mut tokens = Vec::new();
+        stream.process(&mut |token| tokens.push(token.clone()));
+        tokens
+    }
+
+    #[test]
+    fn test_tokenizer() {
+        let jieba = Arc::new(Jieba::new().unwrap());
+        let text = "我来到 北京清华大学。";
+        let mut tokenizer = JiebaTokenizer::new(jieba.clone(), TokenizeMode::Default);
+        let tokens = collect(&mut tokenizer, text);
+        let words: Vec<(&str, usize)> = tokens
+            .iter()
+            .map(|t| (&text[t.offset_from..t.offset_to], t.position))
+            .collect();
+        assert_eq!(
+            words,
+            vec![("我", 0), ("来到", 1), ("北京", 2), ("清华大学", 3)]
+        );
+        assert!(tokens
+            .iter()
+            .all(|t| t.text == text[t.offset_from..t.offset_to]));
+
+        //标点不会被索引
+        let words: Vec<String> = collect(&mut tokenizer, "你好，iPhone 12 - 3.5%。")
+            .into_iter()
+            .map(|t| t.text)
+            .collect();
+        assert_eq!(words, vec!["你好", "iPhone", "12", "3.5%"]);
+
+        let mut tokenizer = JiebaTokenizer::new(jieba, TokenizeMode::Search);
+        let tokens = collect(&mut tokenizer, text);
+        let words: Vec<(&str, usize)> = tokens
+            .iter()
+            .map(|t| (&text[t.offset_from..t.offset_to], t.position))
+            .collect();
+        assert_eq!(
+            words,
+            vec![
+                ("我", 0),
+                ("来到", 1),
+                ("北京", 2),
+                ("清华", 3),
+                ("华大", 3),
+                ("大学", 3),
+                ("清华大学", 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_no_hmm() {
+        //不使用 HMM 时字母数字串后紧跟汉字
+        let jieba = Arc::new(Jieba::new().unwrap());
+        let mut tokenizer = JiebaTokenizer::new(jieba, TokenizeMode::Search);
+        tokenizer.set_hmm(false);
+        let text = "abc中国 iPhone12手机";
+        let tokens = collect(&mut tokenizer, text);
+        assert!(tokens
+            .iter()
+            .all(|t| t.text == text[t.offset_from..t.offset_to]));
+        let words: Vec<&str> = tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(words, vec!["abc", "中国", "iPhone12", "手机"]);
+    }
+}